@@ -0,0 +1,137 @@
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, OnceLock};
+
+use crate::error::{Context, Result, SummariserError};
+use crate::{progress, utils};
+
+/// Whether a cassette records live API responses or replays saved ones
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum CassetteMode {
+    /// Replay if the cassette file exists, otherwise record
+    Auto,
+    /// Call the API and save every request/response pair
+    Record,
+    /// Serve responses from the cassette without calling the API
+    Replay,
+}
+
+/// A single recorded request and the content returned for it
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct Interaction {
+    request: Value,
+    response: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    finish_reason: Option<String>,
+}
+
+/// A set of recorded OpenAI interactions backed by a JSON file
+#[derive(Debug)]
+pub struct Cassette {
+    path: PathBuf,
+    replaying: bool,
+    interactions: Mutex<Vec<Interaction>>,
+}
+
+static CASSETTE: OnceLock<Cassette> = OnceLock::new();
+
+/// Loads (or starts) the cassette used by all OpenAI calls for this run
+pub fn install(path: &Path, mode: CassetteMode) -> Result<()> {
+    let replaying = match mode {
+        CassetteMode::Auto => path.exists(),
+        CassetteMode::Record => false,
+        CassetteMode::Replay => true,
+    };
+
+    let interactions = if replaying {
+        let content = utils::read_from_file(path)?;
+        serde_json::from_str(&content)
+            .context(format!("Failed to parse cassette: {}", path.display()))?
+    } else {
+        Vec::new()
+    };
+
+    progress::info(format_args!(
+        "{} OpenAI responses {} cassette: {}",
+        if replaying { "Replaying" } else { "Recording" },
+        if replaying { "from" } else { "to" },
+        path.display()
+    ));
+
+    CASSETTE
+        .set(Cassette {
+            path: path.to_path_buf(),
+            replaying,
+            interactions: Mutex::new(interactions),
+        })
+        .map_err(|_| SummariserError::OpenAiError("A cassette has already been installed".to_string(), None))
+}
+
+/// Returns the cassette for this run, if one was installed
+pub fn current() -> Option<&'static Cassette> {
+    CASSETTE.get()
+}
+
+impl Cassette {
+    /// Whether responses should be served from the cassette instead of the API
+    pub fn is_replaying(&self) -> bool {
+        self.replaying
+    }
+
+    /// Looks up the recorded response (and finish reason, if any) for a request
+    pub fn replay(&self, request: &Value) -> Result<(String, Option<String>)> {
+        let interactions = self.interactions.lock().expect("cassette lock poisoned");
+        interactions
+            .iter()
+            .find(|interaction| &interaction.request == request)
+            .map(|interaction| (interaction.response.clone(), interaction.finish_reason.clone()))
+            .ok_or_else(|| SummariserError::OpenAiError(format!(
+                "No recorded response for this request in cassette: {}",
+                self.path.display()
+            ), None))
+    }
+
+    /// Records a request/response pair and saves the cassette immediately
+    pub fn record(&self, request: Value, response: &str, finish_reason: Option<&str>) -> Result<()> {
+        let mut interactions = self.interactions.lock().expect("cassette lock poisoned");
+        interactions.push(Interaction {
+            request,
+            response: response.to_string(),
+            finish_reason: finish_reason.map(str::to_string),
+        });
+
+        let content = serde_json::to_string_pretty(&*interactions)
+            .context("Failed to serialize cassette")?;
+        utils::save_to_file(&self.path, &content)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_record_then_replay() {
+        let path = std::env::temp_dir().join(format!("cassette-test-{}.json", std::process::id()));
+        let request = serde_json::json!({ "model": "gpt-4", "prompt": "hello" });
+
+        let recorder = Cassette {
+            path: path.clone(),
+            replaying: false,
+            interactions: Mutex::new(Vec::new()),
+        };
+        recorder.record(request.clone(), "world", Some("stop")).unwrap();
+
+        let saved: Vec<Interaction> = serde_json::from_str(&utils::read_from_file(&path).unwrap()).unwrap();
+        let player = Cassette {
+            path: path.clone(),
+            replaying: true,
+            interactions: Mutex::new(saved),
+        };
+        assert_eq!(player.replay(&request).unwrap(), ("world".to_string(), Some("stop".to_string())));
+        assert!(player.replay(&serde_json::json!({ "model": "other" })).is_err());
+
+        std::fs::remove_file(path).ok();
+    }
+}
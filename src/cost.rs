@@ -0,0 +1,78 @@
+use std::ops::AddAssign;
+
+/// Tokens taken by the system prompt, instructions and message framing of each request
+const PROMPT_OVERHEAD_TOKENS: u64 = 150;
+
+/// List prices in USD per million tokens (input, output), matched by model name prefix.
+/// Longer prefixes come first so e.g. "gpt-4o-mini" isn't priced as "gpt-4o" or "gpt-4".
+const PRICES: [(&str, f64, f64); 9] = [
+    ("gpt-4.1-nano", 0.10, 0.40),
+    ("gpt-4.1-mini", 0.40, 1.60),
+    ("gpt-4o-mini", 0.15, 0.60),
+    ("gpt-4-turbo", 10.0, 30.0),
+    ("gpt-4-32k", 60.0, 120.0),
+    ("gpt-4.1", 2.0, 8.0),
+    ("gpt-4o", 2.5, 10.0),
+    ("gpt-4", 30.0, 60.0),
+    ("gpt-3.5-turbo", 0.50, 1.50),
+];
+
+/// Estimated token usage of one or more chat completion requests
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Usage {
+    pub requests: u64,
+    pub input_tokens: u64,
+    /// Upper bound: every request is assumed to use its full max_tokens
+    pub output_tokens: u64,
+}
+
+impl Usage {
+    /// Usage of a single request whose transcript and variable instructions take `input_tokens` tokens
+    pub fn request(input_tokens: usize, max_tokens: u16) -> Self {
+        Usage {
+            requests: 1,
+            input_tokens: input_tokens as u64 + PROMPT_OVERHEAD_TOKENS,
+            output_tokens: u64::from(max_tokens),
+        }
+    }
+
+    /// Adds tokens of other input (e.g. a generated summary) to the request's prompt
+    pub fn with_extra_input(mut self, tokens: u64) -> Self {
+        self.input_tokens += tokens;
+        self
+    }
+
+    /// Estimated cost in USD with the given model, if its price is known
+    pub fn cost(&self, model: &str) -> Option<f64> {
+        let (_, input_price, output_price) = PRICES.iter().find(|(prefix, _, _)| model.starts_with(prefix))?;
+        Some((self.input_tokens as f64 * input_price + self.output_tokens as f64 * output_price) / 1_000_000.0)
+    }
+}
+
+impl AddAssign for Usage {
+    fn add_assign(&mut self, other: Self) {
+        self.requests += other.requests;
+        self.input_tokens += other.input_tokens;
+        self.output_tokens += other.output_tokens;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_usage_cost() {
+        let mut usage = Usage::request(1000, 1000);
+        assert_eq!(usage.input_tokens, 1000 + PROMPT_OVERHEAD_TOKENS);
+        usage += Usage::request(0, 500).with_extra_input(100);
+        assert_eq!(usage.input_tokens, 1100 + 2 * PROMPT_OVERHEAD_TOKENS);
+        assert_eq!(usage.requests, 2);
+        assert_eq!(usage.output_tokens, 1500);
+
+        let usage = Usage { requests: 1, input_tokens: 1_000_000, output_tokens: 1_000_000 };
+        assert_eq!(usage.cost("gpt-4"), Some(90.0));
+        assert_eq!(usage.cost("gpt-4o-mini-2024-07-18"), Some(0.75));
+        assert_eq!(usage.cost("some-local-model"), None);
+    }
+}
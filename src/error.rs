@@ -0,0 +1,175 @@
+use std::fmt::Display;
+
+use crate::transcript::CaptionError;
+
+/// Result type of the fetching, parsing and summarising functions
+pub type Result<T, E = SummariserError> = std::result::Result<T, E>;
+
+/// An error from another crate kept as the cause of a [`SummariserError`]
+pub type Source = Box<dyn std::error::Error + Send + Sync>;
+
+/// What went wrong, so callers can tell e.g. a bad URL from a video without captions
+/// from a network failure. Each variant carries the full message, context included; when
+/// another crate's error caused it, that error is its `source()` rather than part of the message.
+#[derive(Debug, thiserror::Error)]
+pub enum SummariserError {
+    /// The URL isn't a YouTube video or playlist URL
+    #[error("{0}")]
+    InvalidUrl(String),
+    /// The video has no captions that can be used (none at all, or none matching the request)
+    #[error("{0}")]
+    NoCaptions(String),
+    /// The video needs a signed-in user: age-restricted, private or members-only
+    #[error("{0}")]
+    AgeRestricted(String),
+    /// A request to YouTube failed or came back with an error status
+    #[error("{}", message_or(.0, "HTTP request failed"))]
+    HttpError(String, #[source] Option<Source>),
+    /// An OpenAI request failed or returned nothing usable
+    #[error("{}", message_or(.0, "OpenAI request failed"))]
+    OpenAiError(String, #[source] Option<Source>),
+    /// A page, caption track, file or option value couldn't be parsed
+    #[error("{}", message_or(.0, "Parsing failed"))]
+    ParseError(String, #[source] Option<Source>),
+    /// Reading or writing a file failed
+    #[error("{}", message_or(.0, "I/O error"))]
+    Io(String, #[source] Option<Source>),
+}
+
+/// The message, or what kind of error it is for an error converted without any context
+fn message_or<'a>(message: &'a str, default: &'a str) -> &'a str {
+    if message.is_empty() { default } else { message }
+}
+
+impl SummariserError {
+    /// Short machine-readable name of the variant, e.g. for error logs
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Self::InvalidUrl(_) => "invalid_url",
+            Self::NoCaptions(_) => "no_captions",
+            Self::AgeRestricted(_) => "age_restricted",
+            Self::HttpError(..) => "http",
+            Self::OpenAiError(..) => "openai",
+            Self::ParseError(..) => "parse",
+            Self::Io(..) => "io",
+        }
+    }
+
+    /// The same kind of error with `context` in front of its message, keeping its source
+    pub fn with_context(self, context: impl Display) -> Self {
+        let prefix = |message: String| if message.is_empty() {
+            context.to_string()
+        } else {
+            format!("{}: {}", context, message)
+        };
+        match self {
+            Self::InvalidUrl(message) => Self::InvalidUrl(prefix(message)),
+            Self::NoCaptions(message) => Self::NoCaptions(prefix(message)),
+            Self::AgeRestricted(message) => Self::AgeRestricted(prefix(message)),
+            Self::HttpError(message, source) => Self::HttpError(prefix(message), source),
+            Self::OpenAiError(message, source) => Self::OpenAiError(prefix(message), source),
+            Self::ParseError(message, source) => Self::ParseError(prefix(message), source),
+            Self::Io(message, source) => Self::Io(prefix(message), source),
+        }
+    }
+
+    /// The message followed by those of the errors that caused it, like anyhow's `{:#}`,
+    /// for showing the whole error on one line
+    pub fn report(&self) -> String {
+        let mut report = self.to_string();
+        let mut source = std::error::Error::source(self);
+        while let Some(error) = source {
+            report.push_str(&format!(": {}", error));
+            source = error.source();
+        }
+        report
+    }
+}
+
+impl From<std::io::Error> for SummariserError {
+    fn from(error: std::io::Error) -> Self {
+        Self::Io(String::new(), Some(error.into()))
+    }
+}
+
+impl From<reqwest::Error> for SummariserError {
+    fn from(error: reqwest::Error) -> Self {
+        Self::HttpError(String::new(), Some(error.into()))
+    }
+}
+
+impl From<reqwest::header::InvalidHeaderName> for SummariserError {
+    fn from(error: reqwest::header::InvalidHeaderName) -> Self {
+        Self::ParseError(String::new(), Some(error.into()))
+    }
+}
+
+impl From<serde_json::Error> for SummariserError {
+    fn from(error: serde_json::Error) -> Self {
+        Self::ParseError(String::new(), Some(error.into()))
+    }
+}
+
+impl From<regex::Error> for SummariserError {
+    fn from(error: regex::Error) -> Self {
+        Self::ParseError(String::new(), Some(error.into()))
+    }
+}
+
+impl From<async_openai::error::OpenAIError> for SummariserError {
+    fn from(error: async_openai::error::OpenAIError) -> Self {
+        Self::OpenAiError(String::new(), Some(error.into()))
+    }
+}
+
+impl From<CaptionError> for SummariserError {
+    fn from(error: CaptionError) -> Self {
+        let message = error.to_string();
+        match error {
+            CaptionError::LoginRequired { .. } => Self::AgeRestricted(message),
+            CaptionError::Unplayable { .. }
+            | CaptionError::NoCaptions
+            | CaptionError::EmptyTrackList
+            | CaptionError::LiveNow
+            | CaptionError::StreamCaptionsPending => Self::NoCaptions(message),
+            CaptionError::PlayerResponseNotFound | CaptionError::MalformedPlayerResponse(_) => Self::ParseError(message, None),
+        }
+    }
+}
+
+/// Puts a message in front of an error, like `anyhow::Context`, keeping what kind of error it is
+pub trait Context<T> {
+    fn context(self, context: impl Display) -> Result<T>;
+}
+
+impl<T, E: Into<SummariserError>> Context<T> for std::result::Result<T, E> {
+    fn context(self, context: impl Display) -> Result<T> {
+        self.map_err(|error| error.into().with_context(context))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_context_keeps_the_kind() {
+        let error = Err::<(), _>(CaptionError::LoginRequired { reason: "Sign in to confirm your age".to_string() })
+            .context("Failed to list captions")
+            .unwrap_err();
+        assert_eq!(error.kind(), "age_restricted");
+        assert!(error.to_string().starts_with("Failed to list captions: This video requires signing in (Sign in to confirm your age)"));
+
+        let error = Err::<(), _>(std::io::Error::new(std::io::ErrorKind::NotFound, "not found"))
+            .context("Failed to read file: a.txt")
+            .context("Failed to load cassette")
+            .unwrap_err();
+        assert!(matches!(error, SummariserError::Io(..)));
+        assert_eq!(error.to_string(), "Failed to load cassette: Failed to read file: a.txt");
+        assert_eq!(std::error::Error::source(&error).unwrap().to_string(), "not found");
+        assert_eq!(error.report(), "Failed to load cassette: Failed to read file: a.txt: not found");
+
+        let error = SummariserError::from(std::io::Error::new(std::io::ErrorKind::NotFound, "not found"));
+        assert_eq!(error.to_string(), "I/O error");
+    }
+}
@@ -0,0 +1,199 @@
+//! Fetches YouTube transcripts and summarises them with OpenAI.
+//!
+//! [`summarise_video`] runs the whole pipeline for one video; the modules expose each step
+//! (fetching captions, generating the summary, highlights and other outputs, saving them)
+//! for programs that need more control. The `youtube-summariser` binary is built on top.
+
+pub mod cassette;
+pub mod cost;
+pub mod error;
+pub mod openai;
+pub mod progress;
+pub mod search;
+pub mod tokens;
+pub mod transcript;
+pub mod utils;
+
+use std::path::PathBuf;
+
+use error::Context;
+
+pub use error::{Result, SummariserError};
+pub use openai::{GenerationOptions, SummaryLength};
+pub use transcript::{fetch_video_data, FetchOptions, TranscriptFormat, VideoMetadata};
+pub use utils::{extract_video_id, SummaryResult, Workspace};
+
+/// Settings for [`summarise_video`], covering what the command line's main flags do
+#[derive(Debug, Clone)]
+pub struct Options {
+    /// OpenAI model used for the summary, highlights and tags
+    pub model: String,
+    /// Language code of the caption track to use; defaults to the first track's language
+    pub language: Option<String>,
+    /// Language code to write the summary and highlights in, when it differs from the transcript's
+    pub output_language: Option<String>,
+    /// Directory to cache transcripts in and save result.json to, one subdirectory per video;
+    /// nothing is read or written when `None`
+    pub output_dir: Option<PathBuf>,
+    /// How long the summary should be
+    pub length: SummaryLength,
+    /// Topic the summary should be centred on
+    pub focus: Option<String>,
+    /// Also extract topical tags
+    pub tags: bool,
+    /// Everything else about fetching from YouTube; `language` above takes precedence over its own
+    pub fetch: FetchOptions,
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Options {
+            model: openai::DEFAULT_MODEL.to_string(),
+            language: None,
+            output_language: None,
+            output_dir: None,
+            length: SummaryLength::default(),
+            focus: None,
+            tags: false,
+            fetch: FetchOptions::default(),
+        }
+    }
+}
+
+impl Options {
+    /// The prompt settings for a video, asking for `output_language` only when the transcript
+    /// isn't already in it
+    pub fn generation_options(&self, metadata: &VideoMetadata) -> GenerationOptions {
+        let output_language = self.output_language.clone().filter(|target| {
+            metadata.language.as_deref().is_none_or(|language| !transcript::same_language(language, target))
+        });
+        GenerationOptions {
+            model: self.model.clone(),
+            truncation_notice: openai::DEFAULT_TRUNCATION_NOTICE.to_string(),
+            focus: self.focus.clone(),
+            voice_sample: None,
+            outline: None,
+            max_tokens: None,
+            chunking: true,
+            stop: Vec::new(),
+            count: None,
+            prompt_template: None,
+            output_language,
+            temperature: None,
+            length: self.length,
+            video_context: Some(openai::describe_video(metadata)),
+        }
+    }
+
+    fn fetch_options(&self) -> FetchOptions {
+        FetchOptions {
+            language: self.language.clone().or_else(|| self.fetch.language.clone()),
+            ..self.fetch.clone()
+        }
+    }
+}
+
+/// Reads a video's transcript and metadata from the workspace's cache when they were fetched
+/// with the same options (see [`Workspace::load_cached_metadata_for`]), or else fetches them
+/// and saves them there, as [`fetch_and_save_video`] does
+pub async fn load_or_fetch_video(
+    workspace: Option<&Workspace>,
+    video_id: &str,
+    options: &FetchOptions,
+    format: TranscriptFormat,
+) -> Result<VideoMetadata> {
+    if let Some(cached) = workspace.and_then(|workspace| workspace.load_cached_metadata_for(video_id, options)) {
+        return Ok(cached);
+    }
+    fetch_and_save_video(workspace, video_id, options, format).await
+}
+
+/// Fetches a video's transcript and metadata and, with a workspace, saves them to it along with
+/// the timed transcript file of `format`
+pub async fn fetch_and_save_video(
+    workspace: Option<&Workspace>,
+    video_id: &str,
+    options: &FetchOptions,
+    format: TranscriptFormat,
+) -> Result<VideoMetadata> {
+    let metadata = fetch_video_data(video_id, options).await
+        .context("Failed to fetch video data")?;
+    if let Some(workspace) = workspace {
+        workspace.save_video_files(&metadata, format)
+            .context("Failed to save video files")?;
+    }
+    Ok(metadata)
+}
+
+/// Fetches a video's transcript (or reuses the one cached in `output_dir`), then generates its
+/// summary, highlights and, if asked for, tags. With an `output_dir`, the transcript and
+/// result.json are saved there as the command line does. Needs `OPENAI_API_KEY` (or
+/// `OPENAI_API_BASE`) set, as the binary does.
+pub async fn summarise_video(video_id: &str, options: &Options) -> Result<SummaryResult> {
+    let fetch_options = options.fetch_options();
+    let workspace = options.output_dir.as_ref().map(Workspace::new);
+
+    let metadata = load_or_fetch_video(workspace.as_ref(), video_id, &fetch_options, TranscriptFormat::Plain).await?;
+
+    let generation_options = options.generation_options(&metadata);
+    let summary = openai::generate_summary(&metadata.transcript, &generation_options).await
+        .context("Failed to generate summary")?;
+    let highlights = openai::generate_highlights(&metadata.transcript, &generation_options).await
+        .context("Failed to generate highlights")?;
+    let tags = if options.tags {
+        openai::generate_tags(&metadata.transcript, &generation_options).await
+            .context("Failed to generate tags")?
+    } else {
+        Vec::new()
+    };
+
+    let result = SummaryResult {
+        video_id: metadata.video_id,
+        title: metadata.title,
+        description: metadata.description,
+        language: metadata.language,
+        transcript: metadata.transcript,
+        summary: summary.content,
+        summary_model: generation_options.model.clone(),
+        highlights: highlights.content,
+        highlights_model: generation_options.model.clone(),
+        summary_settings: generation_options.settings(),
+        highlights_settings: generation_options.settings(),
+        tags,
+        generated_at: utils::format_timestamp(None, true),
+    };
+    if let Some(workspace) = &workspace {
+        workspace.save_result(&result)
+            .context("Failed to save result.json")?;
+    }
+    Ok(result)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_generation_options() {
+        let metadata = VideoMetadata {
+            language: Some("pt".to_string()),
+            ..VideoMetadata::for_test("abc", "Olá a todos")
+        };
+        let options = Options {
+            output_language: Some("en".to_string()),
+            length: SummaryLength::Short,
+            ..Options::default()
+        };
+        let generation_options = options.generation_options(&metadata);
+        assert_eq!(generation_options.model, openai::DEFAULT_MODEL);
+        assert_eq!(generation_options.output_language.as_deref(), Some("en"));
+        assert_eq!(generation_options.length, SummaryLength::Short);
+
+        // Already in the requested language, so no translation is asked for
+        let options = Options { output_language: Some("por".to_string()), ..options };
+        assert_eq!(options.generation_options(&metadata).output_language, None);
+
+        let options = Options { language: Some("pt".to_string()), ..Options::default() };
+        assert_eq!(options.fetch_options().language.as_deref(), Some("pt"));
+    }
+}
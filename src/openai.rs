@@ -1,106 +1,1899 @@
-use anyhow::{Context, Result};
 use async_openai::{
     config::OpenAIConfig,
-    types::{ChatCompletionRequestMessageArgs, CreateChatCompletionRequest, Role},
+    error::OpenAIError,
+    types::{
+        AudioInput, ChatCompletionRequestMessage, ChatCompletionRequestMessageArgs, CreateChatCompletionRequest,
+        CreateEmbeddingRequestArgs, CreateTranscriptionRequestArgs, Role, Stop,
+    },
     Client,
 };
 use std::env;
+use std::fs;
+use std::future::Future;
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::OnceLock;
+use tokio::sync::Mutex;
+use tokio::time::Instant;
+use std::time::Duration;
 
-/// Creates and returns an OpenAI client using API key from environment variables
+use crate::cost::Usage;
+use crate::error::{Context, Result, SummariserError};
+use crate::{cassette, progress, tokens, transcript, utils};
+
+/// Model used when none is configured
+pub const DEFAULT_MODEL: &str = "gpt-4";
+
+/// Tokens kept free for the system prompt, instructions and other text around the transcript
+const PROMPT_RESERVE_TOKENS: usize = 1000;
+
+/// Target size of each chunk when summarising a long transcript in parts
+const CHUNK_CHARS: usize = 8000;
+
+/// Overlap between consecutive chunks, so ideas spanning a boundary aren't lost
+const CHUNK_OVERLAP_CHARS: usize = 500;
+
+/// Longest voice sample accepted, so even small context windows leave room for the transcript
+pub const MAX_VOICE_SAMPLE_CHARS: usize = 5000;
+
+/// Default max_tokens for the summary
+pub const SUMMARY_MAX_TOKENS: u16 = 1500;
+
+/// Default max_tokens for the highlights
+pub const HIGHLIGHTS_MAX_TOKENS: u16 = 1000;
+
+/// max_tokens for the summary of each chunk of a long transcript
+const CHUNK_SUMMARY_MAX_TOKENS: u16 = 500;
+
+/// Default max_tokens for the summary of each chapter with `--by-chapter`
+pub const CHAPTER_SUMMARY_MAX_TOKENS: u16 = 400;
+
+/// max_tokens for the glossary
+pub const GLOSSARY_MAX_TOKENS: u16 = 1000;
+
+/// max_tokens for the list of tags
+pub const TAGS_MAX_TOKENS: u16 = 200;
+
+/// Most tags kept from a response
+const MAX_TAGS: usize = 10;
+
+/// Fewest transcript tokens --ask will answer questions from, after leaving room for the answers
+const MIN_QUESTION_TRANSCRIPT_TOKENS: usize = 1000;
+
+/// max_tokens for the show notes, the social posts of each platform, the YouTube comment and the Q&A summary
+pub const EXTRA_MAX_TOKENS: u16 = 800;
+
+/// max_tokens for the context digest that --ask-digest answers questions from
+pub const CONTEXT_DIGEST_MAX_TOKENS: u16 = 1500;
+
+/// max_tokens for the cross-video review --review writes
+pub const REVIEW_MAX_TOKENS: u16 = 1500;
+
+/// The whole reply asked for when a question can't be answered from the context digest
+const NEEDS_TRANSCRIPT_REPLY: &str = "NEEDS_TRANSCRIPT";
+
+/// Phrases of questions after detail that a context digest leaves out, such as exact wording or times
+const DETAIL_QUESTION_PHRASES: [&str; 8] = [
+    "quote", "exact", "verbatim", "word for word", "timestamp", "what time", "which minute", "what minute",
+];
+
+/// Embedding model used for --search when none is configured
+pub const DEFAULT_EMBEDDING_MODEL: &str = "text-embedding-3-small";
+
+/// Most texts sent in one embeddings request
+const EMBEDDING_BATCH_SIZE: usize = 100;
+
+/// Largest file accepted by the Whisper transcription endpoint (25 MB)
+pub const MAX_AUDIO_FILE_BYTES: u64 = 25 * 1024 * 1024;
+
+/// Number of attempts made when OpenAI returns a successful response without content
+const EMPTY_RESPONSE_ATTEMPTS: u32 = 3;
+
+/// Base delay between attempts after an empty response (grows linearly per attempt)
+const EMPTY_RESPONSE_DELAY: Duration = Duration::from_secs(1);
+
+/// Default note appended to the prompt when the transcript has been truncated
+pub const DEFAULT_TRUNCATION_NOTICE: &str =
+    "Note: only the first portion of a longer transcript is provided. Do not imply that the whole video was covered.";
+
+/// Highest sampling temperature the API accepts
+const MAX_TEMPERATURE: f32 = 2.0;
+
+/// Validates a sampling temperature (used as a clap value parser)
+pub fn parse_temperature(value: &str) -> Result<f32> {
+    let temperature: f32 = value.parse()
+        .map_err(|_| SummariserError::ParseError(format!("Invalid temperature: {} (expected a number)", value), None))?;
+    if !(0.0..=MAX_TEMPERATURE).contains(&temperature) {
+        return Err(SummariserError::ParseError(format!("Temperature must be between 0.0 and {:.1}, got {}", MAX_TEMPERATURE, value), None));
+    }
+    Ok(temperature)
+}
+
+/// How long the summary should be
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum SummaryLength {
+    /// A 2-3 sentence TL;DR
+    Short,
+    /// A comprehensive summary with headings and bullet points
+    #[default]
+    Medium,
+    /// A detailed breakdown, section by section
+    Long,
+}
+
+impl SummaryLength {
+    /// Name used in output headers, as given to `--length`
+    pub fn name(&self) -> &'static str {
+        match self {
+            SummaryLength::Short => "short",
+            SummaryLength::Medium => "medium",
+            SummaryLength::Long => "long",
+        }
+    }
+
+    /// Default max_tokens for a summary of this length
+    pub fn max_tokens(&self) -> u16 {
+        match self {
+            SummaryLength::Short => 150,
+            SummaryLength::Medium => SUMMARY_MAX_TOKENS,
+            SummaryLength::Long => 3000,
+        }
+    }
+
+    /// What the summary prompt asks for
+    fn instructions(&self) -> &'static str {
+        match self {
+            SummaryLength::Short => "Please provide a TL;DR of the YouTube video transcript in the next message: 2-3 sentences of plain prose covering its main point, with no headings or lists.",
+            SummaryLength::Medium => "Please provide a comprehensive summary of the YouTube video transcript in the next message. Organize it with appropriate headings and bullet points where relevant.",
+            SummaryLength::Long => "Please provide a detailed summary of the YouTube video transcript in the next message. Give each major topic its own `## ` section, in the order they come up, and cover the arguments, examples, names and numbers given, using bullet points where relevant.",
+        }
+    }
+}
+
+/// Settings shared by the chat completion prompts
+#[derive(Debug, Clone)]
+pub struct GenerationOptions {
+    /// OpenAI model to use for the request
+    pub model: String,
+    /// Note appended to the prompt when the transcript is truncated
+    pub truncation_notice: String,
+    /// Topic the summary should be centred on
+    pub focus: Option<String>,
+    /// Writing sample whose tone and style the summary should match
+    pub voice_sample: Option<String>,
+    /// Section headings the summary must contain, in order
+    pub outline: Option<Vec<String>>,
+    /// Maximum number of tokens to generate (each prompt has its own default)
+    pub max_tokens: Option<u16>,
+    /// Summarise long transcripts in chunks instead of truncating them
+    pub chunking: bool,
+    /// Extra sequences that end generation, on top of the prompt's own end marker
+    pub stop: Vec<String>,
+    /// Number of items to ask for in list-style outputs
+    pub count: Option<u8>,
+    /// Custom prompt replacing the built-in system prompt (see `TRANSCRIPT_PLACEHOLDER`)
+    pub prompt_template: Option<String>,
+    /// Language code the response should be written in, when it differs from the transcript's
+    pub output_language: Option<String>,
+    /// Sampling temperature (each prompt has its own default)
+    pub temperature: Option<f32>,
+    /// How long the summary should be
+    pub length: SummaryLength,
+    /// Title, channel and dates of the video (see `describe_video`), so the response can say
+    /// when and by whom it was published
+    pub video_context: Option<String>,
+}
+
+impl GenerationOptions {
+    /// Number of transcript tokens that fit in the model's context window alongside the rest of
+    /// the prompt, the voice sample and the response (its max_tokens, or the largest default)
+    pub fn transcript_budget(&self) -> usize {
+        let sample_tokens = self.voice_sample.as_deref().map_or(0, |sample| tokens::count_tokens(sample, &self.model));
+        let response_tokens = usize::from(self.max_tokens.unwrap_or(SUMMARY_MAX_TOKENS.max(self.length.max_tokens())));
+        tokens::context_window(&self.model).saturating_sub(PROMPT_RESERVE_TOKENS + response_tokens + sample_tokens)
+    }
+
+    /// max_tokens of the summary: the one given, or the default for its length
+    pub fn summary_max_tokens(&self) -> u16 {
+        self.max_tokens.unwrap_or(self.length.max_tokens())
+    }
+
+    /// The settings that shape the output, as listed in the header of the files written with them
+    /// (e.g. "Model: gpt-4 · Focus: testing · Length: short"), so saved outputs can be matched
+    /// against the options of a later run
+    pub fn settings(&self) -> String {
+        let mut settings = vec![format!("Model: {}", self.model)];
+        if let Some(focus) = &self.focus {
+            settings.push(format!("Focus: {}", focus));
+        }
+        if self.length != SummaryLength::Medium {
+            settings.push(format!("Length: {}", self.length.name()));
+        }
+        if self.prompt_template.is_some() {
+            settings.push("Custom prompt".to_string());
+        }
+        if let Some(language) = &self.output_language {
+            settings.push(format!("Written in: {}", transcript::language_name(language).unwrap_or(language)));
+        }
+        settings.join(" · ")
+    }
+
+    /// Whether the transcript is longer than the budget and has to be chunked or truncated
+    pub fn exceeds_budget(&self, transcript: &str) -> bool {
+        tokens::count_tokens(transcript, &self.model) > self.transcript_budget()
+    }
+}
+
+/// Creates and returns an OpenAI client using the API key and base URL from environment variables
 fn create_openai_client() -> Result<Client<OpenAIConfig>> {
-    // Check if OPENAI_API_KEY is set
-    let api_key = env::var("OPENAI_API_KEY")
-        .context("OPENAI_API_KEY environment variable not set. Please set it in your .env file")?;
+    let config = openai_config(env::var("OPENAI_API_KEY").ok(), env::var("OPENAI_API_BASE").ok())?;
+    let client = Client::with_config(config);
+    match PROXY.get() {
+        Some(proxy) => {
+            let http_client = reqwest::Client::builder()
+                .proxy(proxy.clone())
+                .build()
+                .context("Failed to build HTTP client")?;
+            Ok(client.with_http_client(http_client))
+        }
+        None => Ok(client),
+    }
+}
+
+static PROXY: OnceLock<reqwest::Proxy> = OnceLock::new();
+
+/// Sends every OpenAI request of the run through this proxy rather than the one in the proxy
+/// env vars (see `transcript::parse_proxy` for the URLs accepted)
+pub fn set_proxy(proxy: &str) -> Result<()> {
+    // Only set once per run, from the command line
+    let _ = PROXY.set(transcript::parse_proxy(proxy)?);
+    Ok(())
+}
+
+/// Checks that the API key (or a local API base) is configured, so a run can fail before
+/// fetching anything rather than at its first OpenAI call
+pub fn check_config() -> Result<()> {
+    openai_config(env::var("OPENAI_API_KEY").ok(), env::var("OPENAI_API_BASE").ok()).map(|_| ())
+}
+
+/// Builds the client config, pointing it at an OpenAI-compatible server (e.g. Ollama, LM Studio
+/// or vLLM) when `api_base` is set. The API key is only required for OpenAI itself, as local
+/// servers often don't check it.
+fn openai_config(api_key: Option<String>, api_base: Option<String>) -> Result<OpenAIConfig> {
+    let api_base = api_base.map(|base| base.trim().trim_end_matches('/').to_string()).filter(|base| !base.is_empty());
+    let api_key = api_key.filter(|key| !key.trim().is_empty());
+    let api_key = match (api_key, &api_base) {
+        (Some(api_key), _) => api_key,
+        (None, Some(_)) => String::new(),
+        (None, None) => {
+            return Err(SummariserError::OpenAiError(
+                "OPENAI_API_KEY is not set. Add OPENAI_API_KEY=your-key to a .env file in the current directory (or export it), \
+                 or set OPENAI_API_BASE to use a local OpenAI-compatible server. Use --dry-run to fetch and estimate without a key"
+                    .to_string(),
+                None,
+            ));
+        }
+    };
     
-    // Create a client with the API key
     let config = OpenAIConfig::new().with_api_key(api_key);
-    Ok(Client::with_config(config))
+    Ok(match api_base {
+        Some(api_base) => config.with_api_base(api_base),
+        None => config,
+    })
 }
 
-/// Generates a summary from a transcript using OpenAI
-pub async fn generate_summary(transcript: &str) -> Result<String> {
+/// Truncates a transcript to at most `max_tokens` tokens with the given model.
+/// Returns the (possibly) truncated text and whether truncation occurred.
+pub fn truncate_transcript<'a>(transcript: &'a str, max_tokens: usize, model: &str) -> (&'a str, bool) {
+    let truncated = tokens::truncate_to_tokens(transcript, max_tokens, model);
+    (truncated, truncated.len() < transcript.len())
+}
+
+/// Text generated by a chat completion, with the reason the model stopped
+#[derive(Debug, Clone, PartialEq)]
+pub struct Completion {
+    pub content: String,
+    /// e.g. "stop", or "length" when the output hit the max_tokens limit
+    pub finish_reason: Option<String>,
+}
+
+impl Completion {
+    /// Whether the output was cut off by the max_tokens limit
+    pub fn is_truncated(&self) -> bool {
+        self.finish_reason.as_deref() == Some("length")
+    }
+}
+
+static SHOW_USAGE: AtomicBool = AtomicBool::new(false);
+
+/// Tokens used so far in this run, as reported by the API, per model
+static USAGE_TALLY: std::sync::Mutex<Vec<(String, Usage)>> = std::sync::Mutex::new(Vec::new());
+
+/// Spaces out requests so consecutive ones start at least `min_interval` apart.
+/// Callers wait their turn in order; only the starts are spaced, so a slow request can
+/// still be running when the next one starts.
+#[derive(Debug)]
+struct RateLimiter {
+    min_interval: Duration,
+    next_start: Mutex<Option<Instant>>,
+}
+
+impl RateLimiter {
+    fn new(min_interval: Duration) -> Self {
+        RateLimiter { min_interval, next_start: Mutex::new(None) }
+    }
+
+    /// Waits until the next request may start and reserves the slot after it
+    async fn wait(&self) {
+        let mut next_start = self.next_start.lock().await;
+        if let Some(start) = *next_start {
+            tokio::time::sleep_until(start).await;
+        }
+        *next_start = Some(Instant::now() + self.min_interval);
+    }
+}
+
+static RATE_LIMITER: OnceLock<RateLimiter> = OnceLock::new();
+
+/// Makes every chat request of the run start at least `min_interval` after the previous one
+pub fn set_min_request_interval(min_interval: Duration) {
+    // Only set once per run, from the command line
+    let _ = RATE_LIMITER.set(RateLimiter::new(min_interval));
+}
+
+/// Turns on printing the token usage reported for each chat request
+pub fn set_show_usage(enabled: bool) {
+    SHOW_USAGE.store(enabled, Ordering::Relaxed);
+}
+
+/// The tokens used so far in this run by the chat requests sent to OpenAI (not replayed from
+/// a cassette), as reported by the API, per model
+pub fn usage_tally() -> Vec<(String, Usage)> {
+    USAGE_TALLY.lock().unwrap_or_else(|poisoned| poisoned.into_inner()).clone()
+}
+
+/// The cost in USD of [`usage_tally`], if every model's price is known
+pub fn tallied_cost() -> Option<f64> {
+    usage_tally().iter().map(|(model, usage)| usage.cost(model)).sum()
+}
+
+/// The cost in USD of the usage tallied since `earlier` (a previous [`usage_tally`]), if every
+/// model used since then has a known price
+pub fn tallied_cost_since(earlier: &[(String, Usage)]) -> Option<f64> {
+    usage_tally()
+        .iter()
+        .filter(|entry| !earlier.contains(entry))
+        .map(|(model, usage)| {
+            let earlier_cost = earlier.iter().find(|(name, _)| name == model).map_or(Some(0.0), |(_, usage)| usage.cost(model));
+            usage.cost(model).zip(earlier_cost).map(|(cost, earlier_cost)| cost - earlier_cost)
+        })
+        .sum()
+}
+
+/// Adds a chat request's reported usage to the run's tally
+fn record_usage(model: &str, usage: &async_openai::types::Usage) {
+    let usage = Usage {
+        requests: 1,
+        input_tokens: u64::from(usage.prompt_tokens),
+        output_tokens: u64::from(usage.completion_tokens),
+    };
+    let mut tally = USAGE_TALLY.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+    match tally.iter_mut().find(|(name, _)| name == model) {
+        Some((_, total)) => *total += usage,
+        None => tally.push((model.to_string(), usage)),
+    }
+}
+
+/// Prints the tokens a chat request used, as reported by the API, and their cost
+fn print_usage(model: &str, usage: &async_openai::types::Usage) {
+    let cost = Usage {
+        requests: 1,
+        input_tokens: u64::from(usage.prompt_tokens),
+        output_tokens: u64::from(usage.completion_tokens),
+    }
+    .cost(model);
+    progress::println(format_args!(
+        "OpenAI usage ({}): {} input + {} output tokens{}",
+        model,
+        usage.prompt_tokens,
+        usage.completion_tokens,
+        cost.map_or(String::new(), |cost| format!(", ${:.4}", cost))
+    ));
+}
+
+/// Sends a chat request and returns the content of the first choice.
+/// When a cassette is installed, responses are replayed from or recorded to it.
+async fn send_chat_request(request: CreateChatCompletionRequest) -> Result<Completion> {
+    let cassette = cassette::current();
+    let key = serde_json::to_value(&request).context("Failed to serialize OpenAI request")?;
+    if let Some(cassette) = cassette.filter(|cassette| cassette.is_replaying()) {
+        let (content, finish_reason) = cassette.replay(&key)?;
+        return Ok(Completion { content, finish_reason });
+    }
+    
     let client = create_openai_client()?;
+    let completion = retry_empty_response(EMPTY_RESPONSE_ATTEMPTS, EMPTY_RESPONSE_DELAY, || async {
+        if let Some(rate_limiter) = RATE_LIMITER.get() {
+            rate_limiter.wait().await;
+        }
+        let response = client.chat().create(request.clone()).await
+            .map_err(|e| explain_model_error(e, &request.model))?;
+        if let Some(usage) = &response.usage {
+            record_usage(&request.model, usage);
+            if SHOW_USAGE.load(Ordering::Relaxed) {
+                print_usage(&request.model, usage);
+            }
+        }
+        
+        // Extract the content and finish reason from the response
+        Ok(response.choices.first().and_then(|choice| {
+            Some(Completion {
+                content: choice.message.content.clone()?,
+                finish_reason: choice.finish_reason.clone(),
+            })
+        }))
+    })
+    .await?;
     
-    // Truncate transcript if it's too long (OpenAI has token limits)
-    let truncated_transcript = if transcript.len() > 10000 {
-        // Truncate to approximately 10k chars (about 2.5k tokens)
-        // In real-world scenarios, you might want to chunk the transcript
-        &transcript[0..10000]
-    } else {
-        transcript
-    };
+    if let Some(cassette) = cassette {
+        cassette.record(key, &completion.content, completion.finish_reason.as_deref())?;
+    }
     
-    // Create the chat completion request
-    let request = CreateChatCompletionRequest {
-        model: "gpt-4".to_string(),
-        messages: vec![
-            ChatCompletionRequestMessageArgs::default()
-                .role(Role::System)
-                .content("You are a helpful assistant that generates concise, informative summaries of YouTube video transcripts. Focus on the main points, key insights, and important details. Format your response in Markdown.")
-                .build()?,
+    Ok(completion)
+}
+
+/// Turns an API error into a clear message when OpenAI rejected the model name
+fn explain_model_error(error: OpenAIError, model: &str) -> SummariserError {
+    if let OpenAIError::ApiError(api_error) = &error {
+        let code = api_error.code.as_ref().and_then(|code| code.as_str());
+        if code == Some("model_not_found") || api_error.message.contains("does not exist") {
+            return SummariserError::OpenAiError(format!(
+                "OpenAI rejected the model \"{}\" ({}). Check the name, or pick a model your account can use with --model or OPENAI_MODEL.",
+                model, api_error.message
+            ), None);
+        }
+    }
+    SummariserError::from(error).with_context("Failed to get response from OpenAI API")
+}
+
+/// Repeats a request while it succeeds without content (empty `choices` or `content`),
+/// which OpenAI occasionally returns transiently. Errors are returned immediately.
+async fn retry_empty_response<F, Fut>(attempts: u32, delay: Duration, mut request: F) -> Result<Completion>
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = Result<Option<Completion>>>,
+{
+    for attempt in 1..=attempts {
+        match request().await? {
+            Some(completion) if !completion.content.trim().is_empty() => return Ok(completion),
+            _ if attempt < attempts => {
+                progress::warn(format_args!("OpenAI returned an empty response, retrying ({}/{})...", attempt, attempts - 1));
+                tokio::time::sleep(delay * attempt).await;
+            }
+            _ => {}
+        }
+    }
+    
+    Err(SummariserError::OpenAiError(format!("No content received from OpenAI after {} attempts", attempts), None))
+}
+
+/// Number of words in each segment picked by `sample_transcript`
+const SAMPLE_SEGMENT_WORDS: usize = 40;
+
+/// Picks an evenly-spaced sample of transcript segments covering `percent` of the text,
+/// so a short prompt still spans the whole video rather than just its opening.
+/// Segments are fixed-size word windows, joined with an ellipsis to mark the gaps.
+pub fn sample_transcript(transcript: &str, percent: u8) -> String {
+    let words: Vec<&str> = transcript.split_whitespace().collect();
+    let segments: Vec<String> = words.chunks(SAMPLE_SEGMENT_WORDS).map(|chunk| chunk.join(" ")).collect();
+    if segments.is_empty() || percent >= 100 {
+        return transcript.to_string();
+    }
+    
+    let count = (segments.len() * percent as usize).div_ceil(100).max(1);
+    (0..count)
+        .map(|i| segments[i * segments.len() / count].as_str())
+        .collect::<Vec<_>>()
+        .join(" … ")
+}
+
+/// Builds the transcript part of a prompt, appending the truncation notice if the transcript
+/// had to be cut to fit the budget (and saying whether it was)
+fn fit_transcript(transcript: &str, options: &GenerationOptions) -> (String, bool) {
+    let (truncated_transcript, truncated) = truncate_transcript(transcript, options.transcript_budget(), &options.model);
+    if truncated {
+        (format!("{}\n\n{}", truncated_transcript, options.truncation_notice), true)
+    } else {
+        (truncated_transcript.to_string(), false)
+    }
+}
+
+/// Builds the transcript part of a prompt, warning when part of the transcript is left out
+fn prepare_transcript(transcript: &str, options: &GenerationOptions) -> String {
+    let (prompt, truncated) = fit_transcript(transcript, options);
+    if truncated {
+        progress::warn(format_args!(
+            "Warning: the transcript is {} tokens but only {} fit in {}'s context window; the rest is left out",
+            tokens::count_tokens(transcript, &options.model),
+            options.transcript_budget(),
+            options.model
+        ));
+    }
+    prompt
+}
+
+/// Assembles the chat messages for a prompt.
+///
+/// The stable parts (system prompt, then the task instructions) always come first and are
+/// identical across videos, while everything that varies per run (focus, transcript) goes
+/// in the final message. OpenAI caches prompts by prefix, so batch runs that share the
+/// same prompts get cache hits on the stable portion, reducing latency and input cost.
+fn build_messages(system_prompt: &str, instructions: &str, content: String) -> Result<Vec<ChatCompletionRequestMessage>> {
+    Ok(vec![
+        ChatCompletionRequestMessageArgs::default()
+            .role(Role::System)
+            .content(system_prompt)
+            .build()?,
+        ChatCompletionRequestMessageArgs::default()
+            .role(Role::User)
+            .content(instructions)
+            .build()?,
+        ChatCompletionRequestMessageArgs::default()
+            .role(Role::User)
+            .content(content)
+            .build()?,
+    ])
+}
+
+/// Placeholder that makes a prompt template the whole request, with the transcript put in its place
+pub const TRANSCRIPT_PLACEHOLDER: &str = "{transcript}";
+
+/// Assembles the chat messages for a prompt, letting a custom template override the built-in one.
+///
+/// A template replaces the system prompt and keeps the built-in instructions. If it contains
+/// `TRANSCRIPT_PLACEHOLDER` it is instead sent on its own as the user message, with the
+/// content substituted for the placeholder, replacing the built-in instructions too.
+fn build_templated_messages(options: &GenerationOptions, system_prompt: &str, instructions: &str, content: String) -> Result<Vec<ChatCompletionRequestMessage>> {
+    match &options.prompt_template {
+        None => build_messages(system_prompt, instructions, content),
+        Some(template) if template.contains(TRANSCRIPT_PLACEHOLDER) => Ok(vec![
             ChatCompletionRequestMessageArgs::default()
                 .role(Role::User)
-                .content(format!("Please provide a comprehensive summary of the following YouTube video transcript. Organize it with appropriate headings and bullet points where relevant:\n\n{}", truncated_transcript))
+                .content(template.replace(TRANSCRIPT_PLACEHOLDER, &content))
                 .build()?,
-        ],
-        temperature: Some(0.7),
-        max_tokens: Some(1500),
+        ]),
+        Some(template) => build_messages(template, instructions, content),
+    }
+}
+
+/// Describes a video for the prompts: its title and whichever of its channel, dates, length
+/// and view count are known
+pub fn describe_video(metadata: &transcript::VideoMetadata) -> String {
+    let details = &metadata.details;
+    let mut lines = vec![format!("Title: {}", metadata.title)];
+    if let Some(channel) = &details.channel {
+        lines.push(format!("Channel: {}", channel));
+    }
+    if let Some(date) = details.publish_date.as_ref().or(details.upload_date.as_ref()) {
+        lines.push(format!("Published: {}", date));
+    }
+    if let Some(duration) = details.duration {
+        lines.push(format!("Duration: {}", transcript::format_youtube_timestamp(duration as f64)));
+    }
+    if let Some(view_count) = details.view_count {
+        lines.push(format!("Views: {}", utils::format_count(view_count)));
+    }
+    lines.join("\n")
+}
+
+/// Builds the description of the video, if any, which the response can draw on to say who
+/// published it and when
+fn video_instruction(options: &GenerationOptions) -> String {
+    match &options.video_context {
+        Some(context) => format!(
+            "About the video (mention who published it and when where that helps the reader, but don't invent anything not given here):\n{}\n\n",
+            context
+        ),
+        None => String::new(),
+    }
+}
+
+/// Builds the instruction asking the summary to match the voice sample, if any
+fn voice_instruction(options: &GenerationOptions) -> String {
+    match &options.voice_sample {
+        Some(sample) => format!(
+            "Write the summary in the voice of the following writing sample, matching its tone, style and vocabulary (but not its content):\n\n---\n{}\n---\n\n",
+            sample.trim()
+        ),
+        None => String::new(),
+    }
+}
+
+/// Builds the instruction to answer in the output language, if any
+fn language_instruction(options: &GenerationOptions) -> String {
+    match &options.output_language {
+        Some(code) => {
+            let language = transcript::language_name(code).map_or_else(|| format!("the language with code \"{}\"", code), str::to_string);
+            format!(
+                "Write your entire response in {}, whatever the language of the transcript. Keep names, quotes and technical terms in their original form where a translation would be unclear.\n\n",
+                language
+            )
+        }
+        None => String::new(),
+    }
+}
+
+/// Builds the extra instruction that weights a summary toward the focus topic, if any
+fn focus_instruction(options: &GenerationOptions) -> String {
+    match &options.focus {
+        Some(focus) => format!(
+            "Focus topic: \"{}\". Still cover the whole video, but emphasise content relevant to this topic and go into more detail on it. If the video barely covers this topic, say so explicitly at the start.\n\nTranscript:\n\n",
+            focus
+        ),
+        None => String::new(),
+    }
+}
+
+/// Generates a summary from a transcript using OpenAI
+/// Long transcripts are summarised in overlapping chunks that are then merged, unless chunking is disabled.
+pub async fn generate_summary(transcript: &str, options: &GenerationOptions) -> Result<Completion> {
+    // Transcripts over the budget are chunked, or truncated if chunking is disabled (OpenAI has token limits)
+    let truncated_transcript = if options.chunking && options.exceeds_budget(transcript) {
+        summarize_chunked(transcript, options).await?
+    } else {
+        prepare_transcript(transcript, options)
+    };
+    
+    let mut instructions = options.length.instructions().to_string();
+    if let Some(outline) = &options.outline {
+        instructions.push_str(&format!(
+            " Structure the summary using exactly these sections, as `## ` headings in this order, and no other top-level sections:\n{}",
+            outline.iter().map(|heading| format!("## {}", heading)).collect::<Vec<_>>().join("\n")
+        ));
+    }
+    
+    // Create the chat completion request
+    let mut request = CreateChatCompletionRequest {
+        model: options.model.clone(),
+        messages: build_templated_messages(
+            options,
+            "You are a helpful assistant that generates concise, informative summaries of YouTube video transcripts. Focus on the main points, key insights, and important details. Format your response in Markdown.",
+            &instructions,
+            format!(
+                "{}{}{}{}{}",
+                language_instruction(options),
+                video_instruction(options),
+                voice_instruction(options),
+                focus_instruction(options),
+                truncated_transcript
+            ),
+        )?,
+        temperature: Some(options.temperature.unwrap_or(0.7)),
+        max_tokens: Some(options.summary_max_tokens()),
         ..Default::default()
     };
     
     // Send the request to the OpenAI API
-    let response = client.chat().create(request).await
-        .context("Failed to get response from OpenAI API")?;
+    let summary = send_chat_request(request.clone()).await?;
+    let Some(outline) = &options.outline else {
+        return Ok(summary);
+    };
+    
+    // Re-prompt once if the model skipped any of the required sections
+    let missing = missing_outline_sections(&summary.content, outline);
+    if missing.is_empty() {
+        return Ok(summary);
+    }
+    progress::status(format_args!("Summary is missing outline sections ({}), asking again...", missing.join(", ")));
+    request.messages.push(
+        ChatCompletionRequestMessageArgs::default()
+            .role(Role::Assistant)
+            .content(summary.content)
+            .build()?,
+    );
+    request.messages.push(
+        ChatCompletionRequestMessageArgs::default()
+            .role(Role::User)
+            .content(format!(
+                "Your summary is missing these required sections: {}. Rewrite the full summary so it contains every required `## ` section in order.",
+                missing.join(", ")
+            ))
+            .build()?,
+    );
+    
+    let summary = send_chat_request(request).await?;
+    let missing = missing_outline_sections(&summary.content, outline);
+    if !missing.is_empty() {
+        progress::warn(format_args!("Warning: summary is still missing outline sections: {}", missing.join(", ")));
+    }
+    Ok(summary)
+}
+
+/// Generates the summary of one chapter of a video using OpenAI
+pub async fn generate_chapter_summary(chapter_title: &str, chapter_transcript: &str, options: &GenerationOptions) -> Result<Completion> {
+    let request = CreateChatCompletionRequest {
+        model: options.model.clone(),
+        messages: build_templated_messages(
+            options,
+            "You are a helpful assistant that summarises one chapter of a YouTube video at a time. Cover only what is said in the chapter you are given. Format your response in Markdown.",
+            "Summarise the chapter of a YouTube video transcript in the next message in a short paragraph or a few bullet points. Do not add a heading: the chapter title is already shown above your summary.",
+            format!(
+                "{}{}{}Chapter: {}\n\n{}",
+                language_instruction(options),
+                voice_instruction(options),
+                focus_instruction(options),
+                chapter_title,
+                prepare_transcript(chapter_transcript, options)
+            ),
+        )?,
+        temperature: Some(options.temperature.unwrap_or(0.7)),
+        max_tokens: Some(options.max_tokens.unwrap_or(CHAPTER_SUMMARY_MAX_TOKENS)),
+        ..Default::default()
+    };
+    
+    send_chat_request(request).await
+}
+
+/// Estimates the token usage of `generate_summary`, counting a request per chunk (and the
+/// final merge) when the transcript is chunked. Outline re-prompts aren't included.
+pub fn estimate_summary_usage(transcript: &str, options: &GenerationOptions) -> Usage {
+    let max_tokens = options.summary_max_tokens();
+    let instructions = format!("{}{}{}", language_instruction(options), voice_instruction(options), focus_instruction(options));
+    let extra_tokens = tokens::count_tokens(&instructions, &options.model);
+    if !(options.chunking && options.exceeds_budget(transcript)) {
+        let transcript_tokens = tokens::count_tokens(&fit_transcript(transcript, options).0, &options.model);
+        return Usage::request(transcript_tokens + extra_tokens, max_tokens);
+    }
+    
+    let chunk_chars = CHUNK_CHARS.min(options.transcript_budget());
+    let chunks = chunk_transcript(transcript, chunk_chars, CHUNK_OVERLAP_CHARS.min(chunk_chars / 4));
+    let mut usage = Usage::default();
+    for chunk in &chunks {
+        usage += Usage::request(tokens::count_tokens(chunk, &options.model), CHUNK_SUMMARY_MAX_TOKENS);
+    }
+    
+    // Partial summaries too long for the final pass together are merged in batches first,
+    // assuming each one uses its full max_tokens
+    let budget = options.transcript_budget();
+    let mut notes = vec![usize::from(CHUNK_SUMMARY_MAX_TOKENS); chunks.len()];
+    while notes.len() > 1 && notes.iter().sum::<usize>() > budget {
+        let batches = note_batches(&notes, budget);
+        for batch in &batches {
+            usage += Usage::request(notes[batch.clone()].iter().sum(), CHUNK_SUMMARY_MAX_TOKENS);
+        }
+        notes = vec![usize::from(CHUNK_SUMMARY_MAX_TOKENS); batches.len()];
+    }
     
-    // Extract the summary from the response
-    if let Some(choice) = response.choices.first() {
-        if let Some(content) = &choice.message.content {
-            return Ok(content.clone());
+    // The final pass reads the remaining notes
+    usage += Usage::request(extra_tokens, max_tokens).with_extra_input(notes.iter().sum::<usize>() as u64);
+    usage
+}
+
+/// Estimates the token usage of a single request over the (fitted) transcript
+pub fn estimate_transcript_usage(transcript: &str, options: &GenerationOptions, default_max_tokens: u16) -> Usage {
+    Usage::request(
+        tokens::count_tokens(&fit_transcript(transcript, options).0, &options.model),
+        options.max_tokens.unwrap_or(default_max_tokens),
+    )
+}
+
+/// Summarises each overlapping chunk of a long transcript and returns the partial
+/// summaries, ready for a final "summary of summaries" pass
+async fn summarize_chunked(transcript: &str, options: &GenerationOptions) -> Result<String> {
+    // Every token spans at least one byte, so a chunk of at most `budget` bytes always fits
+    let chunk_chars = CHUNK_CHARS.min(options.transcript_budget());
+    let chunks = chunk_transcript(transcript, chunk_chars, CHUNK_OVERLAP_CHARS.min(chunk_chars / 4));
+    
+    let mut partial_summaries = Vec::with_capacity(chunks.len());
+    for (index, chunk) in chunks.iter().enumerate() {
+        progress::status(format_args!("Summarising part {}/{} of the transcript...", index + 1, chunks.len()));
+        let request = CreateChatCompletionRequest {
+            model: options.model.clone(),
+            messages: build_messages(
+                "You are a helpful assistant that summarises one part of a longer YouTube video transcript. Your notes will be combined with notes on the other parts into a single summary.",
+                "Summarise the transcript excerpt in the next message as concise bullet points. Keep the key points, names, numbers and any new or surprising claims. Do not add an introduction or conclusion.",
+                format!("Part {} of {}:\n\n{}", index + 1, chunks.len(), chunk),
+            )?,
+            temperature: Some(options.temperature.unwrap_or(0.5)),
+            max_tokens: Some(CHUNK_SUMMARY_MAX_TOKENS),
+            ..Default::default()
+        };
+        
+        let completion = send_chat_request(request).await
+            .context(format!("Failed to summarise part {} of the transcript", index + 1))?;
+        partial_summaries.push(format!("### Part {} of {}\n\n{}", index + 1, chunks.len(), completion.content.trim()));
+    }
+    
+    // Notes too long for the final pass together are merged in batches of consecutive parts,
+    // again and again, until they fit
+    let budget = options.transcript_budget();
+    loop {
+        let note_tokens: Vec<usize> = partial_summaries.iter().map(|note| tokens::count_tokens(note, &options.model)).collect();
+        if partial_summaries.len() <= 1 || note_tokens.iter().sum::<usize>() <= budget {
+            break;
         }
+        partial_summaries = merge_notes(&partial_summaries, &note_batches(&note_tokens, budget), options).await?;
     }
     
-    Err(anyhow::anyhow!("No content received from OpenAI"))
+    Ok(format!(
+        "The transcript was too long to process at once, so it was split into {} consecutive parts and each part was summarised. Treat these partial summaries as the transcript and combine them into one summary of the whole video, removing repetition from the overlapping parts:\n\n{}",
+        chunks.len(),
+        partial_summaries.join("\n\n")
+    ))
+}
+
+/// Groups consecutive notes of the given token counts into batches that each fit in `budget`.
+/// Every batch but a lone last note holds at least two, so merging them always shortens the list.
+fn note_batches(note_tokens: &[usize], budget: usize) -> Vec<std::ops::Range<usize>> {
+    let mut batches = Vec::new();
+    let mut start = 0;
+    while start < note_tokens.len() {
+        let mut end = start + 1;
+        let mut total = note_tokens[start];
+        while end < note_tokens.len() && (end - start < 2 || total + note_tokens[end] <= budget) {
+            total += note_tokens[end];
+            end += 1;
+        }
+        batches.push(start..end);
+        start = end;
+    }
+    batches
+}
+
+/// Merges each batch of consecutive partial summaries into one shorter set of notes
+async fn merge_notes(notes: &[String], batches: &[std::ops::Range<usize>], options: &GenerationOptions) -> Result<Vec<String>> {
+    let mut merged = Vec::with_capacity(batches.len());
+    for (index, batch) in batches.iter().enumerate() {
+        if batch.len() == 1 {
+            merged.push(notes[batch.start].clone());
+            continue;
+        }
+        progress::status(format_args!("Merging partial summaries {}-{} of {}...", batch.start + 1, batch.end, notes.len()));
+        let request = CreateChatCompletionRequest {
+            model: options.model.clone(),
+            messages: build_messages(
+                "You are a helpful assistant that condenses notes on consecutive parts of a longer YouTube video transcript. Your notes will be combined with notes on the other parts into a single summary.",
+                "Combine the notes in the next message into one set of concise bullet points, in order. Keep the key points, names, numbers and any new or surprising claims, and remove repetition. Do not add an introduction or conclusion.",
+                notes[batch.clone()].join("\n\n"),
+            )?,
+            temperature: Some(options.temperature.unwrap_or(0.5)),
+            max_tokens: Some(CHUNK_SUMMARY_MAX_TOKENS),
+            ..Default::default()
+        };
+        
+        let completion = send_chat_request(request).await
+            .context(format!("Failed to merge partial summaries {}-{}", batch.start + 1, batch.end))?;
+        merged.push(format!("### Merged notes {} of {}\n\n{}", index + 1, batches.len(), completion.content.trim()));
+    }
+    Ok(merged)
+}
+
+/// Splits a transcript into overlapping windows of at most `chunk_chars` bytes,
+/// preferring to break at sentence ends (then whitespace) and never inside a UTF-8 character
+fn chunk_transcript(transcript: &str, chunk_chars: usize, overlap_chars: usize) -> Vec<&str> {
+    let mut chunks = Vec::new();
+    let mut start = 0;
+    while start < transcript.len() {
+        let mut end = (start + chunk_chars).min(transcript.len());
+        while !transcript.is_char_boundary(end) {
+            end -= 1;
+        }
+        
+        if end < transcript.len() {
+            // Only look for a break in the second half of the window so chunks stay reasonably large
+            let window = &transcript[start..end];
+            let min_break = window.len() / 2;
+            let sentence_break = window.rmatch_indices(['.', '?', '!'])
+                .map(|(index, _)| index + 1)
+                .find(|&index| index > min_break);
+            let space_break = window.rfind(char::is_whitespace).filter(|&index| index > min_break);
+            if let Some(index) = sentence_break.or(space_break) {
+                end = start + index;
+            }
+        }
+        
+        chunks.push(transcript[start..end].trim());
+        if end >= transcript.len() {
+            break;
+        }
+        
+        // Start the next chunk a little before this one ended, at a word boundary
+        let mut next = end.saturating_sub(overlap_chars).max(start + 1);
+        while !transcript.is_char_boundary(next) {
+            next += 1;
+        }
+        if let Some(index) = transcript[next..end].find(char::is_whitespace) {
+            next += index;
+        }
+        start = next;
+    }
+    
+    chunks.retain(|chunk| !chunk.is_empty());
+    chunks
+}
+
+/// Parses an outline file into section headings, one per line (leading `#`s are optional)
+pub fn parse_outline(content: &str) -> Vec<String> {
+    content
+        .lines()
+        .map(|line| line.trim().trim_start_matches('#').trim())
+        .filter(|heading| !heading.is_empty())
+        .map(str::to_string)
+        .collect()
+}
+
+/// Returns the outline headings that don't appear as a Markdown heading in the summary
+fn missing_outline_sections<'a>(summary: &str, outline: &'a [String]) -> Vec<&'a str> {
+    let headings: Vec<String> = summary
+        .lines()
+        .filter(|line| line.trim_start().starts_with('#'))
+        .map(|line| line.trim().trim_start_matches('#').trim().to_lowercase())
+        .collect();
+    
+    outline
+        .iter()
+        .map(String::as_str)
+        .filter(|heading| !headings.contains(&heading.to_lowercase()))
+        .collect()
 }
 
 /// Generates highlights of new or unusual information from a transcript using OpenAI
-pub async fn generate_highlights(transcript: &str) -> Result<String> {
-    let client = create_openai_client()?;
+pub async fn generate_highlights(transcript: &str, options: &GenerationOptions) -> Result<Completion> {
+    // Truncate transcript if it's too long (OpenAI has token limits)
+    let truncated_transcript = prepare_transcript(transcript, options);
+    
+    let mut instructions = format!(
+        "Analyze the transcript in the next message and identify any new, unique, or unusual information. Highlight key insights that might not be widely known or that represent innovative thinking. Format your response with appropriate headings and emphasis. When you have finished, write {} on its own line.",
+        HIGHLIGHTS_END_MARKER
+    );
+    if let Some(count) = options.count {
+        instructions.push_str(&format!(" List at most {} highlights.", count));
+    }
+    
+    // Create the chat completion request
+    let request = CreateChatCompletionRequest {
+        model: options.model.clone(),
+        messages: build_templated_messages(
+            options,
+            "You are a specialist at identifying and highlighting new, unique, or unusual information from video transcripts. Focus on extracting insights that are not commonly known or that represent innovative thinking. Format your response in Markdown.",
+            &instructions,
+            format!("{}{}{}", language_instruction(options), video_instruction(options), truncated_transcript),
+        )?,
+        temperature: Some(options.temperature.unwrap_or(0.7)),
+        max_tokens: Some(options.max_tokens.unwrap_or(HIGHLIGHTS_MAX_TOKENS)),
+        stop: Some(Stop::StringArray(highlights_stop_sequences(options)?)),
+        ..Default::default()
+    };
     
+    // Send the request to the OpenAI API
+    send_chat_request(request).await
+}
+
+/// Marker the highlights prompt asks the model to write when it's done; generation stops on it.
+/// It never appears in ordinary Markdown, so it can't cut a legitimate list short.
+const HIGHLIGHTS_END_MARKER: &str = "END_OF_HIGHLIGHTS";
+
+/// Maximum number of `--stop` sequences (OpenAI accepts four, one is the end marker)
+pub const MAX_EXTRA_STOP_SEQUENCES: usize = 3;
+
+/// The highlights end marker followed by any user-supplied stop sequences
+fn highlights_stop_sequences(options: &GenerationOptions) -> Result<Vec<String>> {
+    if options.stop.len() > MAX_EXTRA_STOP_SEQUENCES {
+        return Err(SummariserError::ParseError(format!(
+            "At most {} stop sequences can be given, got {}",
+            MAX_EXTRA_STOP_SEQUENCES,
+            options.stop.len()
+        ), None));
+    }
+    let mut stop = vec![HIGHLIGHTS_END_MARKER.to_string()];
+    stop.extend(options.stop.iter().cloned());
+    Ok(stop)
+}
+
+/// Generates a glossary of specialised terms used in a transcript using OpenAI.
+/// Returns (term, definition) pairs; the list is empty when the video has little jargon.
+pub async fn generate_glossary(transcript: &str, options: &GenerationOptions) -> Result<Vec<(String, String)>> {
     // Truncate transcript if it's too long (OpenAI has token limits)
-    let truncated_transcript = if transcript.len() > 10000 {
-        &transcript[0..10000]
-    } else {
-        transcript
+    let truncated_transcript = prepare_transcript(transcript, options);
+    
+    // Create the chat completion request
+    let request = CreateChatCompletionRequest {
+        model: options.model.clone(),
+        messages: build_messages(
+            "You are a technical editor who builds glossaries for video transcripts. Identify specialised terms, acronyms and jargon that a general audience may not know, and define each one briefly and accurately in the context of the video. Do not list general topics or everyday words.",
+            "Extract the specialised terms used in the transcript in the next message. Reply with one term per line in the exact format `Term: definition`, with no other text. If the transcript uses no specialised terms, reply with `NONE`.",
+            truncated_transcript,
+        )?,
+        temperature: Some(0.3),
+        max_tokens: Some(GLOSSARY_MAX_TOKENS),
+        ..Default::default()
+    };
+    
+    // Send the request to the OpenAI API
+    let response = send_chat_request(request).await?;
+    Ok(parse_glossary(&response.content))
+}
+
+/// Generates 5-10 topical tags for a transcript using OpenAI, e.g. for indexing notes
+pub async fn generate_tags(transcript: &str, options: &GenerationOptions) -> Result<Vec<String>> {
+    // Truncate transcript if it's too long (OpenAI has token limits)
+    let truncated_transcript = prepare_transcript(transcript, options);
+    
+    // Create the chat completion request
+    let request = CreateChatCompletionRequest {
+        model: options.model.clone(),
+        messages: build_messages(
+            "You are a librarian who tags videos by topic so they can be found again later. Pick specific topics, technologies, people and fields the video is about, not generic words like \"video\" or \"discussion\".",
+            "Give 5 to 10 tags for the video whose transcript is in the next message. Reply with only a JSON array of lowercase strings, e.g. [\"rust\", \"memory safety\"].",
+            truncated_transcript,
+        )?,
+        temperature: Some(0.3),
+        max_tokens: Some(TAGS_MAX_TOKENS),
+        ..Default::default()
     };
     
+    // Send the request to the OpenAI API
+    let response = send_chat_request(request).await?;
+    Ok(parse_tags(&response.content))
+}
+
+/// Summarises the audience Q&A of a talk using OpenAI, as the questions asked and their answers.
+/// `qa_transcript` should be just the Q&A part of the transcript.
+pub async fn generate_qa_summary(qa_transcript: &str, options: &GenerationOptions) -> Result<String> {
+    // Truncate transcript if it's too long (OpenAI has token limits)
+    let truncated_transcript = prepare_transcript(qa_transcript, options);
+    
     // Create the chat completion request
     let request = CreateChatCompletionRequest {
-        model: "gpt-4".to_string(),
-        messages: vec![
-            ChatCompletionRequestMessageArgs::default()
-                .role(Role::System)
-                .content("You are a specialist at identifying and highlighting new, unique, or unusual information from video transcripts. Focus on extracting insights that are not commonly known or that represent innovative thinking. Format your response in Markdown.")
-                .build()?,
-            ChatCompletionRequestMessageArgs::default()
-                .role(Role::User)
-                .content(format!("Analyze the following transcript and identify any new, unique, or unusual information. Highlight key insights that might not be widely known or that represent innovative thinking. Format your response with appropriate headings and emphasis:\n\n{}", truncated_transcript))
-                .build()?,
-        ],
+        model: options.model.clone(),
+        messages: build_messages(
+            "You are a helpful assistant that summarises the audience Q&A of conference talks. Be accurate and never invent questions or answers. Format your response in Markdown.",
+            "The next message is the end of a talk transcript, which should contain its audience Q&A. List each question asked as a `### ` heading, paraphrased, followed by a short summary of the answer. If the excerpt contains no questions from the audience, say so in one sentence and briefly summarise what it covers instead.",
+            truncated_transcript,
+        )?,
+        temperature: Some(0.5),
+        max_tokens: Some(EXTRA_MAX_TOKENS),
+        ..Default::default()
+    };
+    
+    // Send the request to the OpenAI API
+    Ok(send_chat_request(request).await?.content)
+}
+
+/// Options to fit the transcript for `count` questions with. Every answer stays in the
+/// conversation, so room is left for all of them next to the transcript; fails when that leaves
+/// less than `MIN_QUESTION_TRANSCRIPT_TOKENS` for the transcript itself
+fn question_fit_options(count: usize, options: &GenerationOptions) -> Result<GenerationOptions> {
+    let answers_tokens = usize::from(EXTRA_MAX_TOKENS) * count;
+    let fit_options = GenerationOptions {
+        max_tokens: Some(u16::try_from(answers_tokens).unwrap_or(u16::MAX)),
+        ..options.clone()
+    };
+    let budget = fit_options.transcript_budget();
+    if budget < MIN_QUESTION_TRANSCRIPT_TOKENS {
+        return Err(SummariserError::OpenAiError(format!(
+            "Too many questions for {}: the answers to {} question(s) would leave {} tokens of its context window for the transcript (at least {} needed). Ask fewer questions at once or use a model with a larger context window",
+            options.model, count, budget, MIN_QUESTION_TRANSCRIPT_TOKENS
+        ), None));
+    }
+    Ok(fit_options)
+}
+
+/// Answers questions about a video from its transcript using OpenAI.
+/// The questions are asked in order in one conversation, so later ones can refer to earlier answers.
+pub async fn answer_questions(transcript: &str, questions: &[String], options: &GenerationOptions) -> Result<Vec<String>> {
+    answer_questions_after(transcript, &[], &[], questions, options).await
+}
+
+/// Like [`answer_questions`], but continuing a conversation in which `asked` were already
+/// answered with `answered`, so the new questions can refer back to them
+async fn answer_questions_after(
+    transcript: &str,
+    asked: &[String],
+    answered: &[String],
+    questions: &[String],
+    options: &GenerationOptions,
+) -> Result<Vec<String>> {
+    // Earlier answers take up the context window like the new ones will
+    let fit_options = question_fit_options(answered.len() + questions.len(), options)?;
+    let truncated_transcript = prepare_transcript(transcript, &fit_options);
+    
+    let mut messages = build_messages(
+        "You are a helpful assistant that answers questions about a video using only its transcript. If the transcript doesn't answer a question, say so rather than guessing. Format your response in Markdown.",
+        "The next message is the transcript of a video. Answer each question that follows from it, citing what was said.",
+        truncated_transcript,
+    )?;
+    for (question, answer) in asked.iter().zip(answered) {
+        messages.push(ChatCompletionRequestMessageArgs::default()
+            .role(Role::User)
+            .content(question.as_str())
+            .build()?);
+        messages.push(ChatCompletionRequestMessageArgs::default()
+            .role(Role::Assistant)
+            .content(answer.as_str())
+            .build()?);
+    }
+    let mut answers = Vec::with_capacity(questions.len());
+    for question in questions {
+        messages.push(ChatCompletionRequestMessageArgs::default()
+            .role(Role::User)
+            .content(question.as_str())
+            .build()?);
+        let request = CreateChatCompletionRequest {
+            model: options.model.clone(),
+            messages: messages.clone(),
+            temperature: Some(0.3),
+            max_tokens: Some(EXTRA_MAX_TOKENS),
+            ..Default::default()
+        };
+        
+        let answer = send_chat_request(request).await?.content;
+        messages.push(ChatCompletionRequestMessageArgs::default()
+            .role(Role::Assistant)
+            .content(answer.as_str())
+            .build()?);
+        answers.push(answer);
+    }
+    
+    Ok(answers)
+}
+
+/// Whether a question asks for detail that a context digest leaves out, such as quotes or the
+/// time something was said, so it's answered from the full transcript straight away
+pub fn needs_full_transcript(question: &str) -> bool {
+    let question = question.to_lowercase();
+    DETAIL_QUESTION_PHRASES.iter().any(|phrase| question.contains(phrase))
+}
+
+/// Generates a compact digest of a transcript for answering questions from: every topic, claim,
+/// name and figure, without the wording, so a Q&A session doesn't resend the whole transcript
+pub async fn generate_context_digest(transcript: &str, options: &GenerationOptions) -> Result<Completion> {
+    let digest_options = GenerationOptions { max_tokens: Some(CONTEXT_DIGEST_MAX_TOKENS), ..options.clone() };
+    let truncated_transcript = prepare_transcript(transcript, &digest_options);
+    
+    let request = CreateChatCompletionRequest {
+        model: options.model.clone(),
+        messages: build_messages(
+            "You are a research assistant who condenses video transcripts into dense reference notes that questions about the video can later be answered from. Keep every topic, claim, argument, example, name, number and recommendation; drop filler, repetition and the speakers' wording.",
+            "Write reference notes for the transcript in the next message, as terse Markdown bullet points grouped under short headings in the order the video covers them.",
+            truncated_transcript,
+        )?,
+        temperature: Some(0.2),
+        max_tokens: Some(CONTEXT_DIGEST_MAX_TOKENS),
+        ..Default::default()
+    };
+    send_chat_request(request).await
+}
+
+/// Answers questions about a video from its context digest (see [`generate_context_digest`]),
+/// asked in order in one conversation like [`answer_questions`]. A question the digest can't
+/// answer, or one that [`needs_full_transcript`], is answered from the full transcript instead.
+pub async fn answer_questions_from_digest(
+    digest: &str,
+    transcript: &str,
+    questions: &[String],
+    options: &GenerationOptions,
+) -> Result<Vec<String>> {
+    let mut messages = build_messages(
+        "You are a helpful assistant that answers questions about a video using only reference notes taken from its transcript. Format your response in Markdown.",
+        &format!(
+            "The next message holds reference notes on a video's transcript. Answer each question that follows from them. If the notes don't hold enough detail to answer a question, such as the exact wording, reply with only {}.",
+            NEEDS_TRANSCRIPT_REPLY
+        ),
+        digest.to_string(),
+    )?;
+    let mut answers = Vec::with_capacity(questions.len());
+    for (index, question) in questions.iter().enumerate() {
+        messages.push(ChatCompletionRequestMessageArgs::default()
+            .role(Role::User)
+            .content(question.as_str())
+            .build()?);
+        let answer = if needs_full_transcript(question) {
+            None
+        } else {
+            let request = CreateChatCompletionRequest {
+                model: options.model.clone(),
+                messages: messages.clone(),
+                temperature: Some(0.3),
+                max_tokens: Some(EXTRA_MAX_TOKENS),
+                ..Default::default()
+            };
+            Some(send_chat_request(request).await?.content).filter(|answer| !answer.contains(NEEDS_TRANSCRIPT_REPLY))
+        };
+        let answer = match answer {
+            Some(answer) => answer,
+            None => {
+                progress::status("Answering from the full transcript...");
+                // Along with the questions and answers so far, so it can still refer back to them
+                answer_questions_after(transcript, &questions[..index], &answers, std::slice::from_ref(question), options)
+                    .await?
+                    .remove(0)
+            }
+        };
+        // Kept in the conversation either way, so later questions can refer to it
+        messages.push(ChatCompletionRequestMessageArgs::default()
+            .role(Role::Assistant)
+            .content(answer.as_str())
+            .build()?);
+        answers.push(answer);
+    }
+    
+    Ok(answers)
+}
+
+/// Generates podcast-style show notes (hook, key takeaways, mentioned resources) using OpenAI.
+/// The existing summary is passed alongside the transcript so the notes stay consistent with it.
+pub async fn generate_show_notes(transcript: &str, summary: &str, options: &GenerationOptions) -> Result<String> {
+    // Truncate transcript if it's too long (OpenAI has token limits)
+    let truncated_transcript = prepare_transcript(transcript, options);
+    
+    // Create the chat completion request
+    let request = CreateChatCompletionRequest {
+        model: options.model.clone(),
+        messages: build_messages(
+            "You are an editor who writes show notes for podcasts and videos, ready to publish alongside an episode. Write in an engaging but accurate tone and never invent content that is not in the source. Format your response in Markdown.",
+            "Write show notes for the video whose summary and transcript are in the next message. Use exactly this structure: a single hook sentence on the first line (no heading), then a `## Key Takeaways` section with 3-7 bullet points, then a `## Resources Mentioned` section listing any books, tools, websites, papers or people referred to. Omit the `## Resources Mentioned` section entirely if nothing is mentioned.",
+            format!("Summary:\n\n{}\n\nTranscript:\n\n{}", summary, truncated_transcript),
+        )?,
         temperature: Some(0.7),
-        max_tokens: Some(1000),
+        max_tokens: Some(EXTRA_MAX_TOKENS),
+        ..Default::default()
+    };
+    
+    // Send the request to the OpenAI API
+    Ok(send_chat_request(request).await?.content)
+}
+
+/// Writes a review of several videos (e.g. a channel's month) from their summaries using OpenAI:
+/// the themes running through them and the videos that stand out. `period` describes the dates
+/// they cover, e.g. "2024-05-01 to 2024-05-31".
+pub async fn generate_review(entries: &[utils::DigestEntry], period: &str, options: &GenerationOptions) -> Result<Completion> {
+    let summaries: Vec<String> = entries.iter()
+        .map(|entry| {
+            let date = entry.details.publish_date.as_ref().or(entry.details.upload_date.as_ref());
+            format!("## {} ({})\n\n{}", entry.title, date.map_or("date unknown", String::as_str), entry.summary.trim())
+        })
+        .collect();
+    // Summaries past the context window are cut off like a long transcript
+    let review_options = GenerationOptions { max_tokens: Some(REVIEW_MAX_TOKENS), ..options.clone() };
+    let truncated_summaries = prepare_transcript(&summaries.join("\n\n"), &review_options);
+    
+    let request = CreateChatCompletionRequest {
+        model: options.model.clone(),
+        messages: build_messages(
+            "You are an editor writing a review of a creator's recent videos for their audience. Draw only on the summaries you are given and never invent content. Format your response in Markdown.",
+            "The next message holds the summaries of the videos published in a period, each under its title and date. Write a review of the period with a `## Themes` section on the topics and ideas that run through several videos and how they developed, then a `## Standout videos` section naming the few most notable videos by title and saying why. Don't list every video; that list is added separately.",
+            format!("Period: {}\n\n{}", period, truncated_summaries),
+        )?,
+        temperature: Some(0.5),
+        max_tokens: Some(REVIEW_MAX_TOKENS),
+        ..Default::default()
+    };
+    send_chat_request(request).await
+}
+
+/// Writes timestamped key points for a YouTube comment using OpenAI.
+/// `timeline` is either the description's chapter list or a transcript with timestamps,
+/// and is the only source of timestamps the model may use.
+pub async fn generate_comment(summary: &str, timeline: &str, options: &GenerationOptions) -> Result<String> {
+    // Truncate the timeline if it's too long (OpenAI has token limits)
+    let truncated_timeline = prepare_transcript(timeline, options);
+    
+    // Create the chat completion request
+    let request = CreateChatCompletionRequest {
+        model: options.model.clone(),
+        messages: build_messages(
+            "You are a creator writing a pinned YouTube comment that lets viewers jump to the key moments of a video. Write plain text only: no Markdown, no headings, no emoji.",
+            "Using the summary and timeline in the next message, list the key moments of the video in order, one per line, in exactly this form: `M:SS Topic — one-sentence summary` (use `H:MM:SS` past the first hour). Start with `0:00`. Only use timestamps that appear in the timeline, and write nothing else.",
+            format!("Summary:\n\n{}\n\nTimeline:\n\n{}", summary, truncated_timeline),
+        )?,
+        temperature: Some(0.5),
+        max_tokens: Some(EXTRA_MAX_TOKENS),
+        ..Default::default()
+    };
+    
+    // Send the request to the OpenAI API
+    Ok(send_chat_request(request).await?.content)
+}
+
+/// Social media platforms that promotional posts can be written for
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum SocialPlatform {
+    /// A short thread of tweets
+    Twitter,
+    /// A single LinkedIn post
+    Linkedin,
+}
+
+impl SocialPlatform {
+    /// Display name used in headings
+    pub fn name(&self) -> &'static str {
+        match self {
+            SocialPlatform::Twitter => "Twitter / X",
+            SocialPlatform::Linkedin => "LinkedIn",
+        }
+    }
+
+    /// Maximum number of characters allowed in a single post
+    pub fn char_limit(&self) -> usize {
+        match self {
+            SocialPlatform::Twitter => 280,
+            SocialPlatform::Linkedin => 3000,
+        }
+    }
+
+    /// Platform-specific guidance on length and tone
+    fn instructions(&self) -> String {
+        match self {
+            SocialPlatform::Twitter => format!(
+                "Write a thread of 3 to 5 tweets promoting the video. Be punchy and conversational, use at most two hashtags in total, and keep every tweet under {} characters. Separate tweets with a line containing only `---`, and output nothing else.",
+                self.char_limit()
+            ),
+            SocialPlatform::Linkedin => format!(
+                "Write a single LinkedIn post promoting the video. Use a professional but approachable tone, open with a strong first line, include 3-5 short takeaways, and keep it under {} characters. Output only the post.",
+                self.char_limit()
+            ),
+        }
+    }
+}
+
+/// Generates promotional posts for a platform from the summary, using OpenAI.
+/// Posts over the platform's character limit trigger one re-prompt.
+pub async fn generate_social_posts(title: &str, summary: &str, platform: SocialPlatform, options: &GenerationOptions) -> Result<Vec<String>> {
+    // Create the chat completion request
+    let mut request = CreateChatCompletionRequest {
+        model: options.model.clone(),
+        messages: build_messages(
+            "You are a social media manager who writes posts promoting videos. Only make claims supported by the video summary you are given, and never invent quotes or statistics.",
+            &platform.instructions(),
+            format!("Video title: {}\n\nSummary:\n\n{}", title, summary),
+        )?,
+        temperature: Some(0.8),
+        max_tokens: Some(EXTRA_MAX_TOKENS),
         ..Default::default()
     };
     
     // Send the request to the OpenAI API
-    let response = client.chat().create(request).await
-        .context("Failed to get response from OpenAI API")?;
+    let response = send_chat_request(request.clone()).await?;
+    let posts = split_social_posts(&response.content);
+    let too_long = posts_over_limit(&posts, platform.char_limit());
+    if too_long.is_empty() {
+        return Ok(posts);
+    }
+    
+    // Re-prompt once if any post exceeds the character limit
+    progress::status(format_args!("{} post(s) exceed the {} character limit, asking again...", too_long.len(), platform.char_limit()));
+    request.messages.push(
+        ChatCompletionRequestMessageArgs::default()
+            .role(Role::Assistant)
+            .content(response.content)
+            .build()?,
+    );
+    request.messages.push(
+        ChatCompletionRequestMessageArgs::default()
+            .role(Role::User)
+            .content(format!(
+                "Post(s) {} are over the {} character limit. Rewrite all posts so each one is within the limit, keeping the same format.",
+                too_long.iter().map(|index| (index + 1).to_string()).collect::<Vec<_>>().join(", "),
+                platform.char_limit()
+            ))
+            .build()?,
+    );
     
-    // Extract the highlights from the response
-    if let Some(choice) = response.choices.first() {
-        if let Some(content) = &choice.message.content {
-            return Ok(content.clone());
+    let response = send_chat_request(request).await?;
+    let posts = split_social_posts(&response.content);
+    let too_long = posts_over_limit(&posts, platform.char_limit());
+    if !too_long.is_empty() {
+        return Err(SummariserError::OpenAiError(format!(
+            "{} post(s) still exceed the {} character limit for {}",
+            too_long.len(),
+            platform.char_limit(),
+            platform.name()
+        ), None));
+    }
+    Ok(posts)
+}
+
+/// Splits a response into individual posts separated by `---` lines
+fn split_social_posts(response: &str) -> Vec<String> {
+    response
+        .split("\n---")
+        .map(|post| post.trim().trim_start_matches('-').trim().to_string())
+        .filter(|post| !post.is_empty())
+        .collect()
+}
+
+/// Returns the indices of posts longer than the character limit
+fn posts_over_limit(posts: &[String], limit: usize) -> Vec<usize> {
+    posts
+        .iter()
+        .enumerate()
+        .filter(|(_, post)| post.chars().count() > limit)
+        .map(|(index, _)| index)
+        .collect()
+}
+
+/// Parses `Term: definition` lines into pairs, tolerating Markdown bullets and bold terms
+fn parse_glossary(response: &str) -> Vec<(String, String)> {
+    response
+        .lines()
+        .filter_map(|line| {
+            let line = line.trim().trim_start_matches(['-', '*', '•']).trim();
+            let (term, definition) = line.split_once(':')?;
+            let term = term.trim().trim_matches('*').trim_matches('`').trim();
+            let definition = definition.trim();
+            if term.is_empty() || definition.is_empty() {
+                return None;
+            }
+            Some((term.to_string(), definition.to_string()))
+        })
+        .collect()
+}
+
+/// Parses the tags from a JSON array in the response, falling back to splitting the text on
+/// lines and commas when the model answers in prose or a list instead.
+/// Tags are trimmed, lowercased and deduplicated, and at most `MAX_TAGS` are kept.
+fn parse_tags(response: &str) -> Vec<String> {
+    let json_tags = response.find('[')
+        .zip(response.rfind(']'))
+        .and_then(|(start, end)| serde_json::from_str::<Vec<String>>(response.get(start..=end)?).ok());
+    let candidates = json_tags.unwrap_or_else(|| {
+        response.split(['\n', ',']).map(str::to_string).collect()
+    });
+    
+    let mut tags: Vec<String> = Vec::new();
+    for candidate in candidates {
+        // Drop list markers, numbering ("1. ", "2) "), quotes and hashtags
+        let tag = candidate.trim().trim_start_matches(['-', '*', '•']).trim();
+        let tag = match tag.split_once(['.', ')']) {
+            Some((number, rest)) if !number.is_empty() && number.chars().all(|c| c.is_ascii_digit()) && rest.starts_with(' ') => rest,
+            _ => tag,
+        };
+        let tag = tag.trim()
+            .trim_start_matches('#')
+            .trim_matches(|c: char| matches!(c, '"' | '\'' | '`' | '*' | '.'))
+            .trim()
+            .to_lowercase();
+        // A sentence of prose isn't a tag
+        if tag.is_empty() || tag.ends_with(':') || tag.split_whitespace().count() > 4 || tags.contains(&tag) {
+            continue;
         }
+        tags.push(tag);
+    }
+    tags.truncate(MAX_TAGS);
+    tags
+}
+
+/// Transcribes a local audio/video file using OpenAI's Whisper endpoint
+pub async fn transcribe_audio(path: &Path) -> Result<String> {
+    let size = fs::metadata(path)
+        .context(format!("Failed to read audio file: {}", path.display()))?
+        .len();
+    if size > MAX_AUDIO_FILE_BYTES {
+        return Err(SummariserError::OpenAiError(format!(
+            "Audio file is {:.1} MB, but the Whisper API accepts at most {} MB. Please compress or split the file first.",
+            size as f64 / (1024.0 * 1024.0),
+            MAX_AUDIO_FILE_BYTES / (1024 * 1024)
+        ), None));
+    }
+    
+    // Audio requests are keyed by file contents, since the path may differ between runs
+    let cassette = cassette::current();
+    let key = serde_json::json!({
+        "endpoint": "audio/transcriptions",
+        "model": "whisper-1",
+        "file": utils::hash_file_contents(path)?,
+    });
+    if let Some(cassette) = cassette.filter(|cassette| cassette.is_replaying()) {
+        return Ok(cassette.replay(&key)?.0);
+    }
+    
+    let client = create_openai_client()?;
+    
+    let request = CreateTranscriptionRequestArgs::default()
+        .file(AudioInput { path: path.to_path_buf() })
+        .model("whisper-1")
+        .build()?;
+    
+    let response = client.audio().transcribe(request).await
+        .context("Failed to get response from OpenAI Whisper API")?;
+    
+    if response.text.trim().is_empty() {
+        return Err(SummariserError::OpenAiError("Whisper returned an empty transcript".to_string(), None));
     }
     
-    Err(anyhow::anyhow!("No content received from OpenAI"))
-}
\ No newline at end of file
+    if let Some(cassette) = cassette {
+        cassette.record(key, &response.text, None)?;
+    }
+    
+    Ok(response.text)
+}
+
+/// Embeds each text with the given embedding model, in batches, returning one vector per text
+pub async fn create_embeddings(texts: &[String], model: &str) -> Result<Vec<Vec<f32>>> {
+    let mut embeddings = Vec::with_capacity(texts.len());
+    for batch in texts.chunks(EMBEDDING_BATCH_SIZE) {
+        embeddings.extend(send_embedding_request(batch, model).await?);
+    }
+    Ok(embeddings)
+}
+
+/// Sends one embeddings request. With a cassette, the vectors are replayed from or recorded to it as JSON.
+async fn send_embedding_request(texts: &[String], model: &str) -> Result<Vec<Vec<f32>>> {
+    let cassette = cassette::current();
+    let key = serde_json::json!({
+        "endpoint": "embeddings",
+        "model": model,
+        "input": texts,
+    });
+    if let Some(cassette) = cassette.filter(|cassette| cassette.is_replaying()) {
+        let (content, _) = cassette.replay(&key)?;
+        return serde_json::from_str(&content).context("Failed to parse recorded embeddings");
+    }
+    
+    if let Some(rate_limiter) = RATE_LIMITER.get() {
+        rate_limiter.wait().await;
+    }
+    let request = CreateEmbeddingRequestArgs::default()
+        .model(model)
+        .input(texts.to_vec())
+        .build()?;
+    let response = create_openai_client()?.embeddings().create(request).await
+        .map_err(|e| explain_model_error(e, model))?;
+    
+    // Vectors come back tagged with the index of their input, which is the order to keep
+    let mut data = response.data;
+    data.sort_by_key(|embedding| embedding.index);
+    let embeddings: Vec<Vec<f32>> = data.into_iter().map(|embedding| embedding.embedding).collect();
+    if embeddings.len() != texts.len() {
+        return Err(SummariserError::OpenAiError(format!(
+            "OpenAI returned {} embeddings for {} texts",
+            embeddings.len(),
+            texts.len()
+        ), None));
+    }
+    
+    if let Some(cassette) = cassette {
+        cassette.record(key, &serde_json::to_string(&embeddings)?, None)?;
+    }
+    Ok(embeddings)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Prompt settings with every option off, for tests to override what they need
+    fn test_options() -> GenerationOptions {
+        GenerationOptions {
+            model: DEFAULT_MODEL.to_string(),
+            truncation_notice: DEFAULT_TRUNCATION_NOTICE.to_string(),
+            focus: None,
+            voice_sample: None,
+            outline: None,
+            max_tokens: None,
+            chunking: false,
+            stop: Vec::new(),
+            count: None,
+            prompt_template: None,
+            output_language: None,
+            temperature: None,
+            length: SummaryLength::Medium,
+            video_context: None,
+        }
+    }
+
+    #[test]
+    fn test_truncate_transcript() {
+        let short = "a short transcript";
+        assert_eq!(truncate_transcript(short, 100, DEFAULT_MODEL), (short, false));
+
+        let long = "é".repeat(10_000);
+        let (truncated, was_truncated) = truncate_transcript(&long, 100, DEFAULT_MODEL);
+        assert!(was_truncated);
+        assert!(tokens::count_tokens(truncated, DEFAULT_MODEL) <= 100);
+
+        let mut options = test_options();
+        let budget = tokens::context_window(DEFAULT_MODEL) - PROMPT_RESERVE_TOKENS - usize::from(SUMMARY_MAX_TOKENS);
+        assert_eq!(options.transcript_budget(), budget);
+        assert!(options.exceeds_budget(&long));
+        let prompt = prepare_transcript(&long, &options);
+        assert!(prompt.ends_with(DEFAULT_TRUNCATION_NOTICE));
+
+        // The voice sample and a larger response count against the transcript budget
+        options.voice_sample = Some(vec!["word"; 1000].join(" "));
+        options.max_tokens = Some(2000);
+        assert_eq!(options.transcript_budget(), budget - 1000 - 500);
+
+        // Models with larger context windows get more of the transcript
+        options.model = "gpt-4o".to_string();
+        assert!(!options.exceeds_budget(&long));
+
+        // A long summary's larger default response leaves less room for the transcript
+        options.voice_sample = None;
+        options.max_tokens = None;
+        let medium_budget = options.transcript_budget();
+        options.length = SummaryLength::Long;
+        assert_eq!(options.summary_max_tokens(), 3000);
+        assert_eq!(options.transcript_budget(), medium_budget - 1500);
+        options.max_tokens = Some(200);
+        assert_eq!(options.summary_max_tokens(), 200);
+    }
+
+    #[test]
+    fn test_parse_glossary() {
+        let response = "- **Transformer**: A neural network architecture based on attention.\nRAG: Retrieval-augmented generation.\n\nNot a definition line";
+        let glossary = parse_glossary(response);
+        assert_eq!(glossary, vec![
+            ("Transformer".to_string(), "A neural network architecture based on attention.".to_string()),
+            ("RAG".to_string(), "Retrieval-augmented generation.".to_string()),
+        ]);
+        assert!(parse_glossary("NONE").is_empty());
+    }
+
+    #[test]
+    fn test_parse_tags() {
+        let expected = vec!["rust".to_string(), "memory safety".to_string(), "webassembly".to_string()];
+        assert_eq!(parse_tags(r#"["Rust", "memory safety", "WebAssembly", "rust"]"#), expected);
+        assert_eq!(parse_tags("Here are the tags:\n```json\n[\"rust\", \"memory safety\", \"webassembly\"]\n```"), expected);
+        
+        // Prose and lists fall back to splitting on lines and commas
+        assert_eq!(parse_tags("Tags:\n1. Rust\n2. Memory safety\n3. #WebAssembly"), expected);
+        assert_eq!(parse_tags("rust, memory safety, webassembly."), expected);
+        assert_eq!(parse_tags("- 3D printing\n- 2) CAD"), vec!["3d printing".to_string(), "cad".to_string()]);
+        assert_eq!(parse_tags(&(1..=12).map(|n| format!("tag{}", n)).collect::<Vec<_>>().join(", ")).len(), MAX_TAGS);
+    }
+
+    #[tokio::test]
+    async fn test_rate_limiter_spaces_requests() {
+        let rate_limiter = RateLimiter::new(Duration::from_millis(30));
+        let start = Instant::now();
+        // The first request goes straight away, the others wait for their slot
+        rate_limiter.wait().await;
+        assert!(start.elapsed() < Duration::from_millis(30));
+        tokio::join!(rate_limiter.wait(), rate_limiter.wait());
+        assert!(start.elapsed() >= Duration::from_millis(60));
+    }
+
+    #[test]
+    fn test_usage_tally() {
+        let reported = |prompt_tokens, completion_tokens| async_openai::types::Usage {
+            prompt_tokens,
+            completion_tokens,
+            total_tokens: prompt_tokens + completion_tokens,
+        };
+        // The only test that records usage, as the tally is shared by the whole run
+        record_usage("gpt-4o-mini", &reported(1_000_000, 0));
+        record_usage("gpt-4o-mini", &reported(0, 1_000_000));
+        assert_eq!(
+            usage_tally(),
+            vec![("gpt-4o-mini".to_string(), Usage { requests: 2, input_tokens: 1_000_000, output_tokens: 1_000_000 })]
+        );
+        assert_eq!(tallied_cost(), Some(0.75));
+        let earlier = usage_tally();
+        assert_eq!(tallied_cost_since(&earlier), Some(0.0));
+        record_usage("gpt-4o-mini", &reported(2_000_000, 0));
+        assert!(tallied_cost_since(&earlier).is_some_and(|cost| (cost - 0.3).abs() < 1e-9));
+        record_usage("unpriced-model", &reported(10, 10));
+        assert_eq!(tallied_cost_since(&earlier), None);
+        assert_eq!(tallied_cost(), None);
+    }
+
+    #[tokio::test]
+    async fn test_retry_empty_response_recovers() {
+        let completion = |content: &str| Completion {
+            content: content.to_string(),
+            finish_reason: Some("stop".to_string()),
+        };
+        let mut responses = vec![None, Some(completion("")), Some(completion("Summary"))].into_iter();
+        let result = retry_empty_response(3, Duration::ZERO, || {
+            let response = responses.next().unwrap();
+            async move { Ok(response) }
+        })
+        .await
+        .unwrap();
+        assert_eq!(result, completion("Summary"));
+    }
+
+    #[tokio::test]
+    async fn test_retry_empty_response_gives_up() {
+        let mut calls = 0;
+        let result = retry_empty_response(2, Duration::ZERO, || {
+            calls += 1;
+            async { Ok(None) }
+        })
+        .await;
+        assert!(result.is_err());
+        assert_eq!(calls, 2);
+    }
+
+    #[test]
+    fn test_outline_sections() {
+        let outline = parse_outline("## Context\nMain Argument\n\n# Conclusion\n");
+        assert_eq!(outline, vec!["Context", "Main Argument", "Conclusion"]);
+
+        let summary = "## Context\nSome context.\n### main argument\nThe argument.";
+        assert_eq!(missing_outline_sections(summary, &outline), vec!["Conclusion"]);
+    }
+
+    #[test]
+    fn test_describe_video() {
+        let metadata = transcript::VideoMetadata {
+            details: transcript::VideoDetails {
+                channel: Some("RustConf".to_string()),
+                duration: Some(3725),
+                upload_date: Some("2024-05-01".to_string()),
+                publish_date: Some("2024-05-03".to_string()),
+                ..Default::default()
+            },
+            ..transcript::VideoMetadata::for_test("abc", "")
+        };
+        assert_eq!(describe_video(&metadata), "Title: Talk\nChannel: RustConf\nPublished: 2024-05-03\nDuration: 1:02:05");
+    }
+
+    #[test]
+    fn test_split_social_posts() {
+        let response = "First tweet 🚀\n---\nSecond tweet\n---\n\n";
+        let posts = split_social_posts(response);
+        assert_eq!(posts, vec!["First tweet 🚀", "Second tweet"]);
+        assert!(posts_over_limit(&posts, 280).is_empty());
+        assert_eq!(posts_over_limit(&posts, 12), vec![0]);
+    }
+
+    #[test]
+    fn test_sample_transcript() {
+        let transcript = (0..400).map(|i| format!("w{}", i)).collect::<Vec<_>>().join(" ");
+        let sample = sample_transcript(&transcript, 20);
+
+        // 10 segments of 40 words; a 20% sample keeps the 1st and 6th
+        assert!(sample.starts_with("w0 "));
+        assert!(sample.contains(" … w200 "));
+        assert!(!sample.contains("w40 "));
+        assert_eq!(sample_transcript(&transcript, 100), transcript);
+    }
+
+    #[test]
+    fn test_chunk_transcript() {
+        let sentence = "Ceci est une phrase complète à propos de l'été. ";
+        let transcript = sentence.repeat(500);
+        let chunks = chunk_transcript(&transcript, 8000, 500);
+
+        assert!(chunks.len() > 1);
+        for chunk in &chunks {
+            assert!(chunk.len() <= 8000);
+            assert!(chunk.ends_with('.'));
+        }
+        // Consecutive chunks overlap
+        let tail = &chunks[0][chunks[0].len() - 100..];
+        assert!(chunks[1].contains(tail));
+
+        assert_eq!(chunk_transcript("short", 8000, 500), vec!["short"]);
+    }
+
+    #[test]
+    fn test_note_batches() {
+        assert_eq!(note_batches(&[500; 5], 1200), vec![0..2, 2..4, 4..5]);
+        assert_eq!(note_batches(&[500; 3], 5000), vec![0..3]);
+        // Even when two notes overflow the budget, batches take two so merging makes progress
+        assert_eq!(note_batches(&[500; 4], 100), vec![0..2, 2..4]);
+    }
+
+    #[test]
+    fn test_estimate_summary_usage_merges_notes_in_batches() {
+        let mut options = GenerationOptions {
+            chunking: true,
+            ..test_options()
+        };
+        let transcript = "Ownership moves values between bindings. ".repeat(5000);
+        let budget = options.transcript_budget();
+        let chunks = chunk_transcript(&transcript, CHUNK_CHARS.min(budget), CHUNK_OVERLAP_CHARS.min(CHUNK_CHARS.min(budget) / 4)).len();
+        // The notes on every chunk don't fit in the final pass together, so they're merged first
+        assert!(chunks * usize::from(CHUNK_SUMMARY_MAX_TOKENS) > budget);
+        let usage = estimate_summary_usage(&transcript, &options);
+        assert!(usage.requests > chunks as u64 + 1);
+
+        // With a context window large enough for all the notes, nothing is merged
+        options.model = "gpt-4o".to_string();
+        let transcript = transcript.repeat(5);
+        let budget = options.transcript_budget();
+        let chunks = chunk_transcript(&transcript, CHUNK_CHARS.min(budget), CHUNK_OVERLAP_CHARS.min(CHUNK_CHARS.min(budget) / 4)).len();
+        assert!(options.exceeds_budget(&transcript));
+        assert_eq!(estimate_summary_usage(&transcript, &options).requests, chunks as u64 + 1);
+    }
+
+    #[test]
+    fn test_explain_model_error() {
+        let rejected = OpenAIError::ApiError(async_openai::error::ApiError {
+            message: "The model `gpt-9` does not exist".to_string(),
+            r#type: Some("invalid_request_error".to_string()),
+            param: None,
+            code: Some(serde_json::json!("model_not_found")),
+        });
+        let message = explain_model_error(rejected, "gpt-9").to_string();
+        assert!(message.contains("rejected the model \"gpt-9\""));
+        assert!(message.contains("OPENAI_MODEL"));
+
+        let other = OpenAIError::InvalidArgument("bad request".to_string());
+        let error = explain_model_error(other, "gpt-4");
+        assert!(matches!(error, SummariserError::OpenAiError(..)));
+        assert_eq!(error.to_string(), "Failed to get response from OpenAI API");
+        assert!(error.report().ends_with("bad request"));
+    }
+
+    #[test]
+    fn test_highlights_stop_sequences() {
+        let mut options = GenerationOptions {
+            stop: vec!["\n11.".to_string()],
+            count: Some(10),
+            ..test_options()
+        };
+        assert_eq!(highlights_stop_sequences(&options).unwrap(), vec![HIGHLIGHTS_END_MARKER, "\n11."]);
+
+        options.stop = vec!["a".to_string(); MAX_EXTRA_STOP_SEQUENCES + 1];
+        assert!(highlights_stop_sequences(&options).is_err());
+    }
+
+    #[test]
+    fn test_parse_temperature() {
+        assert_eq!(parse_temperature("0").unwrap(), 0.0);
+        assert_eq!(parse_temperature("2.0").unwrap(), 2.0);
+        assert!(parse_temperature("2.5").unwrap_err().to_string().contains("between 0.0 and 2.0"));
+        assert!(parse_temperature("-0.1").is_err());
+        assert!(parse_temperature("warm").unwrap_err().to_string().contains("expected a number"));
+    }
+
+    #[test]
+    fn test_openai_config() {
+        use async_openai::config::Config;
+
+        let config = openai_config(Some("sk-test".to_string()), None).unwrap();
+        assert_eq!((config.api_key(), config.api_base()), ("sk-test", "https://api.openai.com/v1"));
+
+        // A custom endpoint doesn't need a key, and a trailing slash is dropped
+        let config = openai_config(None, Some("http://localhost:11434/v1/".to_string())).unwrap();
+        assert_eq!((config.api_key(), config.api_base()), ("", "http://localhost:11434/v1"));
+
+        let error = openai_config(None, None).unwrap_err();
+        assert!(matches!(error, SummariserError::OpenAiError(..)));
+        assert!(error.to_string().contains(".env file"));
+        assert!(openai_config(None, Some(" ".to_string())).is_err());
+        assert!(openai_config(Some("".to_string()), None).is_err());
+    }
+
+    #[test]
+    fn test_build_templated_messages() {
+        let mut options = test_options();
+        let contents = |options: &GenerationOptions| -> Vec<String> {
+            build_templated_messages(options, "system", "instructions", "transcript".to_string())
+                .unwrap()
+                .into_iter()
+                .map(|message| message.content.unwrap_or_default())
+                .collect()
+        };
+        assert_eq!(contents(&options), vec!["system", "instructions", "transcript"]);
+
+        options.prompt_template = Some("Explain like I'm five.".to_string());
+        assert_eq!(contents(&options), vec!["Explain like I'm five.", "instructions", "transcript"]);
+
+        options.prompt_template = Some("Summarise in bullets:\n\n{transcript}".to_string());
+        assert_eq!(contents(&options), vec!["Summarise in bullets:\n\ntranscript"]);
+    }
+
+    #[test]
+    fn test_needs_full_transcript() {
+        assert!(needs_full_transcript("Quote what she said about Rust"));
+        assert!(needs_full_transcript("At what time does the demo start?"));
+        assert!(!needs_full_transcript("What are the main arguments?"));
+    }
+
+    #[test]
+    fn test_question_fit_options() {
+        let options = test_options();
+        let fit_options = question_fit_options(2, &options).unwrap();
+        assert_eq!(fit_options.max_tokens, Some(2 * EXTRA_MAX_TOKENS));
+        assert!(fit_options.transcript_budget() >= MIN_QUESTION_TRANSCRIPT_TOKENS);
+
+        let error = question_fit_options(20, &options).unwrap_err();
+        assert!(matches!(error, SummariserError::OpenAiError(..)));
+    }
+}
@@ -0,0 +1,103 @@
+use indicatif::{ProgressBar, ProgressStyle};
+use std::fmt::Display;
+use std::io::IsTerminal;
+use std::sync::OnceLock;
+use std::time::Duration;
+
+/// How progress through a run is shown
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ProgressMode {
+    /// A spinner on stderr showing the current stage (plain lines instead when stderr isn't a terminal)
+    Bar,
+    /// Each stage on a line of its own
+    Verbose,
+    /// No stages at all, only results, warnings and errors
+    Quiet,
+}
+
+/// The mode in use and, in `Bar` mode, the spinner
+struct Progress {
+    mode: ProgressMode,
+    bar: Option<ProgressBar>,
+}
+
+static PROGRESS: OnceLock<Progress> = OnceLock::new();
+
+/// How often the spinner redraws
+const TICK_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Sets how progress is shown for this run. Without it, as when used as a library,
+/// stages are printed as plain lines.
+pub fn init(mode: ProgressMode) {
+    let mode = if mode == ProgressMode::Bar && !std::io::stderr().is_terminal() { ProgressMode::Verbose } else { mode };
+    let bar = (mode == ProgressMode::Bar).then(|| {
+        let bar = ProgressBar::new_spinner();
+        bar.set_style(ProgressStyle::with_template("{spinner} {prefix}{msg}").unwrap());
+        bar.enable_steady_tick(TICK_INTERVAL);
+        bar
+    });
+    // Only the first call counts, like the other run-wide settings
+    let _ = PROGRESS.set(Progress { mode, bar });
+}
+
+/// The spinner, if one is showing
+fn bar() -> Option<&'static ProgressBar> {
+    PROGRESS.get().and_then(|progress| progress.bar.as_ref())
+}
+
+/// Whether stages and other chatter are left out
+fn is_quiet() -> bool {
+    PROGRESS.get().is_some_and(|progress| progress.mode == ProgressMode::Quiet)
+}
+
+/// Prints with the spinner cleared first and redrawn after, so the two never share a line
+fn with_bar_suspended(print: impl FnOnce()) {
+    match bar() {
+        Some(bar) => bar.suspend(print),
+        None => print(),
+    }
+}
+
+/// Reports the stage the run is at, e.g. "Generating summary...": the spinner's message, or a line
+/// of its own in `Verbose` mode
+pub fn status(message: impl Display) {
+    match bar() {
+        Some(bar) => bar.set_message(message.to_string()),
+        None if is_quiet() => {}
+        None => println!("{}", message),
+    }
+}
+
+/// Prints a line of information that isn't a stage, such as the files a run saved (not in `Quiet` mode)
+pub fn info(message: impl Display) {
+    if !is_quiet() {
+        with_bar_suspended(|| println!("{}", message));
+    }
+}
+
+/// Prints a line of output, such as an estimate or an answer, in every mode
+pub fn println(message: impl Display) {
+    with_bar_suspended(|| println!("{}", message));
+}
+
+/// Prints a warning or error to stderr, in every mode
+pub fn warn(message: impl Display) {
+    with_bar_suspended(|| eprintln!("{}", message));
+}
+
+/// Sets which video of a batch is being processed: shown in front of the spinner's stage,
+/// or as a heading line in `Verbose` mode
+pub fn set_video(index: usize, total: usize, name: &str) {
+    match bar() {
+        Some(bar) => bar.set_prefix(format!("[{}/{}] {}: ", index + 1, total, name)),
+        None if is_quiet() => {}
+        None => println!("\n[{}/{}] {}", index + 1, total, name),
+    }
+}
+
+/// Removes the spinner once the run is over
+pub fn finish() {
+    if let Some(bar) = bar() {
+        bar.finish_and_clear();
+    }
+}
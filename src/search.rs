@@ -0,0 +1,109 @@
+use serde::{Deserialize, Serialize};
+
+use crate::error::Result;
+use crate::openai;
+use crate::transcript::{self, TranscriptSegment};
+
+/// Longest chunk of transcript embedded on its own, in characters: a few sentences, enough
+/// context to judge a match while still pointing close to where it's said
+pub const SEARCH_CHUNK_CHARS: usize = 600;
+
+/// A chunk of the transcript and its embedding
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct EmbeddedChunk {
+    /// Start time in seconds of the chunk's first caption
+    pub start: f64,
+    pub text: String,
+    pub embedding: Vec<f32>,
+}
+
+/// The embedded chunks of a video's transcript, as cached in embeddings.json
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EmbeddingIndex {
+    /// Embedding model the chunks were embedded with
+    pub model: String,
+    pub chunks: Vec<EmbeddedChunk>,
+}
+
+impl EmbeddingIndex {
+    /// Whether the index was made with `model` from exactly these chunks, so it can be reused
+    pub fn matches(&self, model: &str, chunks: &[TranscriptSegment]) -> bool {
+        self.model == model
+            && self.chunks.len() == chunks.len()
+            && self.chunks.iter().zip(chunks).all(|(embedded, chunk)| embedded.start == chunk.start && embedded.text == chunk.text)
+    }
+
+    /// The `top_k` chunks most similar to a query's embedding, best first, with their cosine similarity
+    pub fn search(&self, query: &[f32], top_k: usize) -> Vec<(&EmbeddedChunk, f32)> {
+        let mut scored: Vec<(&EmbeddedChunk, f32)> = self.chunks.iter()
+            .map(|chunk| (chunk, cosine_similarity(&chunk.embedding, query)))
+            .collect();
+        scored.sort_by(|a, b| b.1.total_cmp(&a.1));
+        scored.truncate(top_k);
+        scored
+    }
+}
+
+/// Splits a timed transcript into the chunks that get embedded, each keeping the start time
+/// of its first caption
+pub fn chunk_segments(segments: &[TranscriptSegment]) -> Vec<TranscriptSegment> {
+    transcript::merge_segments(segments, SEARCH_CHUNK_CHARS)
+}
+
+/// Embeds each chunk with the given model
+pub async fn build_index(chunks: &[TranscriptSegment], model: &str) -> Result<EmbeddingIndex> {
+    let texts: Vec<String> = chunks.iter().map(|chunk| chunk.text.clone()).collect();
+    let embeddings = openai::create_embeddings(&texts, model).await?;
+    Ok(EmbeddingIndex {
+        model: model.to_string(),
+        chunks: chunks.iter().zip(embeddings)
+            .map(|(chunk, embedding)| EmbeddedChunk { start: chunk.start, text: chunk.text.clone(), embedding })
+            .collect(),
+    })
+}
+
+/// Cosine similarity of two vectors, or 0 if either is all zeros
+fn cosine_similarity(a: &[f32], b: &[f32]) -> f32 {
+    let dot: f32 = a.iter().zip(b).map(|(x, y)| x * y).sum();
+    let norms = a.iter().map(|x| x * x).sum::<f32>().sqrt() * b.iter().map(|y| y * y).sum::<f32>().sqrt();
+    if norms == 0.0 { 0.0 } else { dot / norms }
+}
+
+/// Link to a YouTube video that starts playing at `start` seconds
+pub fn deep_link(video_id: &str, start: f64) -> String {
+    format!("https://www.youtube.com/watch?v={}&t={}s", video_id, start as u64)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_search() {
+        let chunk = |start: f64, text: &str, embedding: Vec<f32>| EmbeddedChunk { start, text: text.to_string(), embedding };
+        let index = EmbeddingIndex {
+            model: "text-embedding-3-small".to_string(),
+            chunks: vec![
+                chunk(0.0, "intro", vec![1.0, 0.0, 0.0]),
+                chunk(62.5, "ownership", vec![0.0, 1.0, 0.0]),
+                chunk(130.0, "borrowing", vec![0.0, 0.8, 0.6]),
+            ],
+        };
+        let results = index.search(&[0.0, 1.0, 0.1], 2);
+        let texts: Vec<&str> = results.iter().map(|(chunk, _)| chunk.text.as_str()).collect();
+        assert_eq!(texts, ["ownership", "borrowing"]);
+        assert!(results[0].1 > results[1].1);
+        assert_eq!(cosine_similarity(&[0.0, 0.0], &[1.0, 0.0]), 0.0);
+
+        let segments = vec![
+            TranscriptSegment { start: 0.0, duration: 60.0, text: "intro".to_string() },
+            TranscriptSegment { start: 62.5, duration: 60.0, text: "ownership".to_string() },
+            TranscriptSegment { start: 130.0, duration: 60.0, text: "borrowing".to_string() },
+        ];
+        assert!(index.matches("text-embedding-3-small", &segments));
+        assert!(!index.matches("text-embedding-3-large", &segments));
+        assert!(!index.matches("text-embedding-3-small", &segments[..2]));
+
+        assert_eq!(deep_link("dQw4w9WgXcQ", 62.5), "https://www.youtube.com/watch?v=dQw4w9WgXcQ&t=62s");
+    }
+}
@@ -0,0 +1,81 @@
+use tiktoken_rs::tokenizer::{get_tokenizer, Tokenizer};
+use tiktoken_rs::{cl100k_base_singleton, o200k_base_singleton, CoreBPE};
+
+/// Context window sizes in tokens (prompt and response together), matched by model name prefix.
+/// Longer prefixes come first so e.g. "gpt-4o" isn't sized as "gpt-4".
+const CONTEXT_WINDOWS: [(&str, usize); 8] = [
+    ("gpt-4.1", 1_047_576),
+    ("gpt-4o", 128_000),
+    ("gpt-4-turbo", 128_000),
+    ("gpt-4-0125", 128_000),
+    ("gpt-4-1106", 128_000),
+    ("gpt-4-32k", 32_768),
+    ("gpt-4", 8_192),
+    ("gpt-3.5-turbo", 16_385),
+];
+
+/// Context window assumed for models not listed above
+const DEFAULT_CONTEXT_WINDOW: usize = 8_192;
+
+/// Number of tokens a model can handle in one request, prompt and response together
+pub fn context_window(model: &str) -> usize {
+    CONTEXT_WINDOWS.iter()
+        .find(|(prefix, _)| model.starts_with(prefix))
+        .map_or(DEFAULT_CONTEXT_WINDOW, |(_, tokens)| *tokens)
+}
+
+/// The BPE tokenizer a model uses, falling back to GPT-4's for models tiktoken doesn't know
+fn tokenizer(model: &str) -> &'static CoreBPE {
+    match get_tokenizer(model) {
+        Some(Tokenizer::O200kBase) => o200k_base_singleton(),
+        _ => cl100k_base_singleton(),
+    }
+}
+
+/// Counts the tokens `text` takes up with the given model
+pub fn count_tokens(text: &str, model: &str) -> usize {
+    tokenizer(model).encode_ordinary(text).len()
+}
+
+/// Cuts `text` down to its first `max_tokens` tokens with the given model
+pub fn truncate_to_tokens<'a>(text: &'a str, max_tokens: usize, model: &str) -> &'a str {
+    let bpe = tokenizer(model);
+    let tokens = bpe.encode_ordinary(text);
+    if tokens.len() <= max_tokens {
+        return text;
+    }
+
+    // Tokens decode back to the exact bytes they came from, so the kept tokens are a byte
+    // prefix of the text; step back to a char boundary in case one splits a character
+    let mut end = bpe.decode_bytes(&tokens[..max_tokens]).map_or(0, |bytes| bytes.len());
+    while !text.is_char_boundary(end) {
+        end -= 1;
+    }
+    &text[..end]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_truncate_to_tokens() {
+        assert_eq!(context_window("gpt-4o-mini"), 128_000);
+        assert_eq!(context_window("gpt-4-0613"), 8_192);
+        assert_eq!(context_window("some-local-model"), DEFAULT_CONTEXT_WINDOW);
+
+        let text = "Rust is a systems programming language. ".repeat(100);
+        let total = count_tokens(&text, "gpt-4");
+        assert!(total > 100 && total < text.len());
+        assert_eq!(truncate_to_tokens(&text, total, "gpt-4"), text);
+
+        let truncated = truncate_to_tokens(&text, 50, "gpt-4o");
+        assert!(text.starts_with(truncated));
+        assert!(count_tokens(truncated, "gpt-4o") <= 50);
+
+        // Multi-byte characters are never split
+        let accented = "é".repeat(1000);
+        let truncated = truncate_to_tokens(&accented, 10, "gpt-4");
+        assert!(!truncated.is_empty() && truncated.chars().all(|c| c == 'é'));
+    }
+}
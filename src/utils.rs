@@ -1,80 +1,524 @@
-use anyhow::{Context, Result};
+use chrono::format::{Item, StrftimeItems};
+use chrono::{Local, NaiveDate, SecondsFormat, Utc};
 use regex::Regex;
+use serde::{Deserialize, Serialize};
 use std::fs;
+use std::io::{BufRead, BufReader, Write};
 use std::path::{Path, PathBuf};
-use crate::transcript::VideoMetadata;
+use std::sync::LazyLock;
+use std::time::Duration;
+use crate::error::{Context, Result, SummariserError};
+use crate::progress;
+use crate::search::EmbeddingIndex;
+use crate::transcript::{self, CaptionSettings, Chapter, FetchOptions, TranscriptFormat, TranscriptSegment, VideoDetails, VideoMetadata};
 
-/// Extracts the YouTube video ID from various formats of YouTube URLs
+/// Extracts the YouTube video ID from various formats of YouTube URLs.
+/// Anything after the ID (`?t=`, `&list=`, `&feature=`, a `#` fragment, ...) is ignored.
 pub fn extract_video_id(url: &str) -> Result<String> {
-    // Match common YouTube URL patterns
-    let patterns = [
-        r"(?:youtube\.com/watch\?v=|youtu\.be/|youtube\.com/embed/|youtube\.com/v/|youtube\.com/e/|youtube\.com/shorts/)([^&?/\s]{11})",
-        r"youtube\.com/watch.*[\?&]v=([^&?/\s]{11})",
-        r"youtube\.com/shorts/([^&?/\s]{11})"
-    ];
-
-    for pattern in patterns {
-        let regex = Regex::new(pattern).context("Failed to compile regex")?;
-        if let Some(captures) = regex.captures(url) {
-            if let Some(id) = captures.get(1) {
-                return Ok(id.as_str().to_string());
+    // The ID is exactly 11 URL-safe characters, so it must not run on into more of them;
+    // on watch pages `v=` can be any of the query parameters
+    let regex = Regex::new(
+        r"(?:youtu\.be/|youtube(?:-nocookie)?\.com/(?:embed/|v/|e/|shorts/|live/|watch/?\?(?:[^#\s]*&)?v=))([\w-]{11})(?:[^\w-]|$)"
+    ).context("Failed to compile regex")?;
+
+    regex.captures(url)
+        .map(|captures| captures[1].to_string())
+        .ok_or_else(|| SummariserError::InvalidUrl(format!("Could not extract YouTube video ID from URL: {}", url)))
+}
+
+/// Parses a list of videos, given by ID or URL and separated by commas, spaces or newlines,
+/// into their IDs; `#` starts a comment that runs to the end of the line
+pub fn parse_video_ids(list: &str) -> Result<Vec<String>> {
+    let mut video_ids = Vec::new();
+    for line in list.lines() {
+        let line = line.split('#').next().unwrap_or_default();
+        for entry in line.split(|c: char| c == ',' || c.is_whitespace()).filter(|entry| !entry.is_empty()) {
+            let video_id = if entry.len() == 11 && entry.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_') {
+                entry.to_string()
+            } else {
+                extract_video_id(entry)?
+            };
+            if !video_ids.contains(&video_id) {
+                video_ids.push(video_id);
             }
         }
     }
+    Ok(video_ids)
+}
+
+/// A YouTube URL's `list=` parameter
+static PLAYLIST_ID_RE: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"youtube\.com/.*[\?&]list=([\w-]+)").unwrap());
+
+/// Extracts the playlist ID from a YouTube URL's `list=` parameter, if it has one
+pub fn extract_playlist_id(url: &str) -> Option<String> {
+    PLAYLIST_ID_RE.captures(url).map(|captures| captures[1].to_string())
+}
+
+/// Whether a URL should be expanded into its playlist's videos: playlist pages always are,
+/// while a watch URL that also names a playlist (`watch?v=...&list=...`) means just that video
+pub fn is_playlist_url(url: &str) -> bool {
+    extract_playlist_id(url).is_some() && (url.contains("/playlist") || extract_video_id(url).is_err())
+}
+
+/// The channel part of a channel URL's path
+static CHANNEL_PATH_RE: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"youtube\.com/(@[\w.-]+|channel/UC[\w-]{22}|c/[\w.-]+|user/[\w.-]+)(?:[/?#]|$)").unwrap()
+});
+
+/// Extracts what follows youtube.com/ in a channel URL: `@handle`, `channel/UC...`, `c/name`
+/// or `user/name`, or `None` if the URL isn't a channel's
+pub fn extract_channel_path(url: &str) -> Option<String> {
+    CHANNEL_PATH_RE.captures(url).map(|captures| captures[1].to_string())
+}
+
+/// URL of a channel given by URL, handle (with or without the `@`) or channel ID
+pub fn channel_url(channel: &str) -> String {
+    if channel.contains("youtube.com/") {
+        channel.to_string()
+    } else if channel.len() == 24 && channel.starts_with("UC") {
+        format!("https://www.youtube.com/channel/{}", channel)
+    } else {
+        format!("https://www.youtube.com/@{}", channel.trim_start_matches('@'))
+    }
+}
+
+/// Files every processed video directory is expected to contain
+pub const EXPECTED_OUTPUT_FILES: [&str; 5] = [
+    "transcript.txt",
+    "metadata.json",
+    "info.md",
+    "summary.md",
+    "highlights.md",
+];
+
+/// File `--json` writes every result of a video to
+pub const RESULT_FILE: &str = "result.json";
 
-    Err(anyhow::anyhow!("Could not extract YouTube video ID from URL: {}", url))
+/// File `--report` writes the title, description, summary and highlights of a video to
+pub const REPORT_FILE: &str = "report.md";
+
+/// The cross-video review --review writes to the output directory
+pub const REVIEW_FILE: &str = "monthly-review.md";
+
+/// File `--search` caches a video's transcript chunk embeddings in
+pub const EMBEDDINGS_FILE: &str = "embeddings.json";
+
+/// Everything generated for a video, as written to result.json
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SummaryResult {
+    pub video_id: String,
+    pub title: String,
+    pub description: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub language: Option<String>,
+    pub transcript: String,
+    pub summary: String,
+    pub summary_model: String,
+    pub highlights: String,
+    pub highlights_model: String,
+    /// The settings the summary and highlights were generated with, as in their Markdown headers
+    /// (see `GenerationOptions::settings`); empty in result.json files written before they were kept
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub summary_settings: String,
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub highlights_settings: String,
+    /// Topical tags, with `--tags`
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tags: Vec<String>,
+    pub generated_at: String,
 }
 
-/// Creates a directory for a video and returns the path
-pub fn create_video_directory(video_id: &str) -> Result<PathBuf> {
-    let video_dir = Path::new("output").join(video_id);
-    fs::create_dir_all(&video_dir).context(format!("Failed to create directory for video: {}", video_id))?;
-    Ok(video_dir)
+/// Maximum number of characters YouTube accepts in a single comment
+pub const YOUTUBE_COMMENT_CHAR_LIMIT: usize = 10_000;
+
+/// Video metadata as stored in metadata.json (the transcript lives in transcript.txt)
+#[derive(Debug, Serialize, Deserialize)]
+struct CachedMetadata {
+    video_id: String,
+    title: String,
+    description: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    etag: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    language: Option<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    segments: Vec<TranscriptSegment>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    chapters: Vec<Chapter>,
+    #[serde(flatten)]
+    details: VideoDetails,
+    #[serde(flatten)]
+    caption_settings: CaptionSettings,
 }
 
-/// Creates all required files for a video in its directory
-pub fn save_video_files(metadata: &VideoMetadata) -> Result<()> {
-    // Create the video directory
-    let video_dir = create_video_directory(&metadata.video_id)?;
+/// Default root directory for processed videos
+pub const DEFAULT_OUTPUT_DIR: &str = "output";
+
+/// The output directory that processed videos are written to and cached in, one subdirectory per video
+#[derive(Debug, Clone)]
+pub struct Workspace {
+    root: PathBuf,
+}
+
+impl Workspace {
+    /// A workspace rooted at `root` (relative to the current directory, or absolute)
+    pub fn new(root: impl Into<PathBuf>) -> Self {
+        Workspace { root: root.into() }
+    }
+
+    /// The root output directory
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    /// The directory holding a video's files (which may not exist yet)
+    pub fn video_dir(&self, video_id: &str) -> PathBuf {
+        self.root.join(video_id)
+    }
+
+    /// Creates a directory for a video and returns the path
+    pub fn create_video_directory(&self, video_id: &str) -> Result<PathBuf> {
+        let video_dir = self.video_dir(video_id);
+        fs::create_dir_all(&video_dir).context(format!("Failed to create directory for video: {}", video_id))?;
+        Ok(video_dir)
+    }
+
+    /// Creates all required files for a video in its directory
+    pub fn save_video_files(&self, metadata: &VideoMetadata, format: TranscriptFormat) -> Result<()> {
+        // Create the video directory
+        let video_dir = self.create_video_directory(&metadata.video_id)?;
     
-    // Save the transcript
-    save_to_file(&video_dir.join("transcript.txt"), &metadata.transcript)?;
+        // Save the transcript
+        save_to_file(&video_dir.join("transcript.txt"), &metadata.transcript)?;
+        if let Some(raw_transcript) = &metadata.raw_transcript {
+            save_to_file(&video_dir.join("transcript.raw.txt"), raw_transcript)?;
+        }
     
-    // Save the metadata (title and description)
-    let info_content = format!("# {}\n\n{}", metadata.title, metadata.description);
-    save_to_file(&video_dir.join("info.md"), &info_content)?;
+        // Save the timed transcript too, if requested and timing is available
+        if let Some(file_name) = format.file_name() {
+            if metadata.segments.is_empty() {
+                progress::warn(format_args!("Warning: no caption timestamps available, skipping {}", file_name));
+            } else {
+                let content = match format {
+                    TranscriptFormat::Srt => transcript::segments_to_srt(&metadata.segments),
+                    _ => transcript::segments_to_vtt(&metadata.segments),
+                };
+                save_to_file(&video_dir.join(file_name), &content)?;
+            }
+        }
     
-    // Create empty summary and highlights files (to be filled later)
-    save_to_file(&video_dir.join("summary.md"), "")?;
-    save_to_file(&video_dir.join("highlights.md"), "")?;
+        // Save the metadata (title, details and description)
+        let info_content = format!("# {}\n\n{}{}", metadata.title, format_video_details(metadata), metadata.description);
+        save_to_file(&video_dir.join("info.md"), &info_content)?;
     
-    Ok(())
+        // Save the machine-readable metadata so cached runs don't need the network
+        let cached = CachedMetadata {
+            video_id: metadata.video_id.clone(),
+            title: metadata.title.clone(),
+            description: metadata.description.clone(),
+            etag: metadata.etag.clone(),
+            language: metadata.language.clone(),
+            segments: metadata.segments.clone(),
+            chapters: metadata.chapters.clone(),
+            details: metadata.details.clone(),
+            caption_settings: metadata.caption_settings.clone(),
+        };
+        let metadata_json = serde_json::to_string_pretty(&cached)
+            .context("Failed to serialize video metadata")?;
+        save_to_file(&video_dir.join("metadata.json"), &metadata_json)?;
+    
+        Ok(())
+    }
+
+    /// Updates or creates the summary file for a video
+    pub fn save_summary(&self, video_id: &str, summary: &str) -> Result<PathBuf> {
+        let video_dir = self.video_dir(video_id);
+        let summary_path = video_dir.join("summary.md");
+        save_to_file(&summary_path, summary)?;
+        Ok(summary_path)
+    }
+
+    /// Updates or creates the highlights file for a video
+    pub fn save_highlights(&self, video_id: &str, highlights: &str) -> Result<PathBuf> {
+        let video_dir = self.video_dir(video_id);
+        let highlights_path = video_dir.join("highlights.md");
+        save_to_file(&highlights_path, highlights)?;
+        Ok(highlights_path)
+    }
+
+    /// Writes a video's results to result.json
+    pub fn save_result(&self, result: &SummaryResult) -> Result<PathBuf> {
+        let result_json = serde_json::to_string_pretty(result)
+            .context("Failed to serialize result")?;
+        let result_path = self.video_dir(&result.video_id).join(RESULT_FILE);
+        save_to_file(&result_path, &result_json)?;
+        Ok(result_path)
+    }
+
+    /// Writes a video's title, details, description, summary and highlights (and optionally
+    /// its transcript) to report.md
+    pub fn save_report(&self, metadata: &VideoMetadata, summary: &str, highlights: &str, transcript: Option<&str>) -> Result<PathBuf> {
+        let report_path = self.video_dir(&metadata.video_id).join(REPORT_FILE);
+        save_to_file(&report_path, &format_report(metadata, summary, highlights, transcript))?;
+        Ok(report_path)
+    }
+
+    /// Writes a video's topical tags to tags.md
+    pub fn save_tags(&self, video_id: &str, header: &str, tags: &[String]) -> Result<PathBuf> {
+        let mut content = format!("{}\n\n# Tags\n\n", header);
+        for tag in tags {
+            content.push_str(&format!("- {}\n", tag));
+        }
+        self.save_output(video_id, "tags.md", &content)
+    }
+
+    /// Writes a video's transcript chunk embeddings to embeddings.json
+    pub fn save_embedding_index(&self, video_id: &str, index: &EmbeddingIndex) -> Result<PathBuf> {
+        let index_json = serde_json::to_string(index)
+            .context("Failed to serialize embeddings")?;
+        let index_path = self.video_dir(video_id).join(EMBEDDINGS_FILE);
+        save_to_file(&index_path, &index_json)?;
+        Ok(index_path)
+    }
+
+    /// Loads the transcript chunk embeddings cached for a video, if there are any
+    pub fn load_embedding_index(&self, video_id: &str) -> Option<EmbeddingIndex> {
+        let index_json = fs::read_to_string(self.video_dir(video_id).join(EMBEDDINGS_FILE)).ok()?;
+        serde_json::from_str(&index_json).ok()
+    }
+
+    /// Updates or creates an additional output file (e.g. glossary.md) for a video
+    pub fn save_output(&self, video_id: &str, file_name: &str, content: &str) -> Result<PathBuf> {
+        let output_path = self.video_dir(video_id).join(file_name);
+        save_to_file(&output_path, content)?;
+        Ok(output_path)
+    }
+}
+
+/// Renders glossary pairs as a Markdown term→definition list
+pub fn format_glossary(glossary: &[(String, String)]) -> String {
+    let mut content = String::from("# Glossary\n\n");
+    if glossary.is_empty() {
+        content.push_str("_No specialised terminology was identified in this video._\n");
+        return content;
+    }
+    
+    for (term, definition) in glossary {
+        content.push_str(&format!("- **{}**: {}\n", term, definition));
+    }
+    content
+}
+
+/// Formats questions asked with `--ask` and their answers as Markdown
+pub fn format_answers(questions: &[String], answers: &[String]) -> String {
+    let mut content = String::from("# Questions\n");
+    for (question, answer) in questions.iter().zip(answers) {
+        content.push_str(&format!("\n## {}\n\n{}\n", question, answer.trim()));
+    }
+    content
+}
+
+/// A description line starting with a timestamp: the timestamp, then the chapter's title
+static DESCRIPTION_CHAPTER_RE: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"^\(?((?:\d{1,2}:)?\d{1,2}:\d{2})\)?\s*[-–—:]?\s*(\S.*)$").unwrap()
+});
+
+/// Extracts chapter markers (lines starting with a timestamp, e.g. "12:34 Topic") from a video description
+pub fn extract_description_chapters(description: &str) -> Vec<String> {
+    description
+        .lines()
+        .map(str::trim)
+        .filter(|line| DESCRIPTION_CHAPTER_RE.is_match(line))
+        .map(str::to_string)
+        .collect()
+}
+
+/// Parses the description's chapter markers into chapters, in the order they're listed
+pub fn parse_description_chapters(description: &str) -> Vec<Chapter> {
+    extract_description_chapters(description)
+        .iter()
+        .filter_map(|line| {
+            let cap = DESCRIPTION_CHAPTER_RE.captures(line)?;
+            let start = cap[1].split(':').fold(0.0, |total, part| total * 60.0 + part.parse::<f64>().unwrap_or(0.0));
+            Some(Chapter { start, title: cap[2].trim().to_string() })
+        })
+        .collect()
+}
+
+/// Splits comment text into blocks of at most `limit` characters, breaking between lines.
+/// A single line longer than the limit is split on its own.
+pub fn split_comment(text: &str, limit: usize) -> Vec<String> {
+    let mut blocks = Vec::new();
+    let mut current = String::new();
+    for line in text.lines().map(str::trim_end).filter(|line| !line.is_empty()) {
+        let mut line = line.to_string();
+        while line.chars().count() > limit {
+            let split_at = line.char_indices().nth(limit).map_or(line.len(), |(index, _)| index);
+            let rest = line.split_off(split_at);
+            if !current.is_empty() {
+                blocks.push(std::mem::take(&mut current));
+            }
+            blocks.push(line);
+            line = rest;
+        }
+        
+        let needed = current.chars().count() + line.chars().count() + usize::from(!current.is_empty());
+        if needed > limit && !current.is_empty() {
+            blocks.push(std::mem::take(&mut current));
+        }
+        if !current.is_empty() {
+            current.push('\n');
+        }
+        current.push_str(&line);
+    }
+    if !current.is_empty() {
+        blocks.push(current);
+    }
+    blocks
 }
 
-/// Updates or creates the summary file for a video
-pub fn save_summary(video_id: &str, summary: &str) -> Result<PathBuf> {
-    let video_dir = Path::new("output").join(video_id);
-    let summary_path = video_dir.join("summary.md");
-    save_to_file(&summary_path, summary)?;
-    Ok(summary_path)
+/// Assembles comment.txt, marking each block when the comment has to be posted in parts
+pub fn format_comment_blocks(blocks: &[String]) -> String {
+    if blocks.len() == 1 {
+        return format!("{}\n", blocks[0]);
+    }
+    blocks
+        .iter()
+        .enumerate()
+        .map(|(index, block)| format!("----- Comment {}/{} -----\n{}\n", index + 1, blocks.len(), block))
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// Assembles the show notes document, inserting chapters before the resources section.
+/// Sections that aren't available (e.g. chapters without timestamps) are omitted.
+pub fn format_show_notes(title: &str, notes: &str, chapters: &[String]) -> String {
+    let mut content = format!("# {} — Show Notes\n\n", title);
+    if chapters.is_empty() {
+        content.push_str(notes.trim());
+        content.push('\n');
+        return content;
+    }
+    
+    let chapter_section = format!(
+        "## Chapters\n\n{}\n\n",
+        chapters.iter().map(|chapter| format!("- {}", chapter)).collect::<Vec<_>>().join("\n")
+    );
+    match notes.find("## Resources Mentioned") {
+        Some(index) => {
+            content.push_str(notes[..index].trim_end());
+            content.push_str("\n\n");
+            content.push_str(&chapter_section);
+            content.push_str(notes[index..].trim());
+        }
+        None => {
+            content.push_str(notes.trim());
+            content.push_str("\n\n");
+            content.push_str(chapter_section.trim_end());
+        }
+    }
+    content.push('\n');
+    content
 }
 
-/// Updates or creates the highlights file for a video
-pub fn save_highlights(video_id: &str, highlights: &str) -> Result<PathBuf> {
-    let video_dir = Path::new("output").join(video_id);
-    let highlights_path = video_dir.join("highlights.md");
-    save_to_file(&highlights_path, highlights)?;
-    Ok(highlights_path)
+/// Renders social posts as Markdown, one section per platform (threads are numbered)
+pub fn format_social_posts(sections: &[(&str, Vec<String>)]) -> String {
+    let mut content = String::from("# Social Posts\n");
+    for (platform, posts) in sections {
+        content.push_str(&format!("\n## {}\n\n", platform));
+        if posts.len() == 1 {
+            content.push_str(&format!("{}\n", posts[0]));
+            continue;
+        }
+        for (index, post) in posts.iter().enumerate() {
+            content.push_str(&format!("**{}/{}**\n\n{}\n\n", index + 1, posts.len(), post));
+        }
+    }
+    content
 }
 
-/// Saves content to a file, creating directories if they don't exist
+/// Saves content to a file, creating directories if they don't exist. The content goes to a
+/// temporary file in the same directory that is then renamed over the target, so a run killed
+/// mid-write leaves the previous version of the file rather than a truncated one.
 pub fn save_to_file(path: &Path, content: &str) -> Result<()> {
     // Ensure parent directory exists
     if let Some(parent) = path.parent() {
         fs::create_dir_all(parent).context("Failed to create directory")?;
     }
     
-    fs::write(path, content).context(format!("Failed to write to file: {}", path.display()))?;
+    let file_name = path.file_name()
+        .ok_or_else(|| SummariserError::Io(format!("Not a file path: {}", path.display()), None))?;
+    // The process ID keeps concurrent runs from sharing a temporary file
+    let temp_path = path.with_file_name(format!(".{}.{}.tmp", file_name.to_string_lossy(), std::process::id()));
+    let written = write_and_sync(&temp_path, content)
+        .and_then(|()| fs::rename(&temp_path, path));
+    if written.is_err() {
+        fs::remove_file(&temp_path).ok();
+    }
+    written.context(format!("Failed to write to file: {}", path.display()))
+}
+
+/// Writes a file and flushes it to disk, so the rename in `save_to_file` never exposes unwritten data
+fn write_and_sync(path: &Path, content: &str) -> std::io::Result<()> {
+    let mut file = fs::File::create(path)?;
+    file.write_all(content.as_bytes())?;
+    file.sync_all()
+}
+
+/// A file in the system's temporary directory that this process created, deleted when dropped
+#[derive(Debug)]
+pub struct TempFile {
+    path: PathBuf,
+}
+
+impl TempFile {
+    /// Writes `data` to a new file named `<prefix>-<pid>-<random>.<extension>`. The file is
+    /// created exclusively, so an existing file or symlink at that path (e.g. one planted on a
+    /// shared /tmp) is never written through, and concurrent runs each get their own.
+    pub fn create(prefix: &str, extension: &str, data: &[u8]) -> Result<Self> {
+        use std::hash::{BuildHasher, RandomState};
+        let mut attempts = 0;
+        loop {
+            // Every RandomState is seeded with fresh random keys
+            let suffix = RandomState::new().hash_one(attempts);
+            let path = std::env::temp_dir().join(format!("{}-{}-{:016x}.{}", prefix, std::process::id(), suffix, extension));
+            match fs::OpenOptions::new().write(true).create_new(true).open(&path) {
+                Ok(mut file) => {
+                    // From here on, the file is removed however the write ends
+                    let temp_file = TempFile { path };
+                    file.write_all(data)
+                        .context(format!("Failed to write temporary file: {}", temp_file.path.display()))?;
+                    return Ok(temp_file);
+                }
+                Err(e) if e.kind() == std::io::ErrorKind::AlreadyExists && attempts < 10 => attempts += 1,
+                Err(e) => return Err(SummariserError::from(e).with_context(format!("Failed to create temporary file: {}", path.display()))),
+            }
+        }
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+}
+
+impl Drop for TempFile {
+    fn drop(&mut self) {
+        fs::remove_file(&self.path).ok();
+    }
+}
+
+/// Appends a JSON value as a single line to a file (JSON Lines format)
+pub fn append_json_line(path: &Path, value: &serde_json::Value) -> Result<()> {
+    if let Some(parent) = path.parent().filter(|parent| !parent.as_os_str().is_empty()) {
+        fs::create_dir_all(parent).context("Failed to create directory")?;
+    }
+    
+    let mut file = fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)
+        .context(format!("Failed to open file: {}", path.display()))?;
+    writeln!(file, "{}", value).context(format!("Failed to write to file: {}", path.display()))?;
     Ok(())
 }
 
@@ -83,15 +527,456 @@ pub fn read_from_file(path: &Path) -> Result<String> {
     fs::read_to_string(path).context(format!("Failed to read file: {}", path.display()))
 }
 
-/// Checks if a video directory already exists
-pub fn video_exists(video_id: &str) -> bool {
-    let video_dir = Path::new("output").join(video_id);
-    video_dir.exists()
+impl Workspace {
+    /// Scans the output directory and returns each video whose expected files are missing or empty
+    pub fn find_incomplete_outputs(&self) -> Result<Vec<(String, Vec<&'static str>)>> {
+        let output_dir = &self.root;
+        if !output_dir.exists() {
+            return Ok(Vec::new());
+        }
+    
+        let mut incomplete = Vec::new();
+        for entry in fs::read_dir(output_dir).context("Failed to read output directory")? {
+            let entry = entry.context("Failed to read output directory entry")?;
+            if !entry.path().is_dir() {
+                continue;
+            }
+        
+            // Videos processed with --json-only or --report-only have their summary and highlights
+            // in result.json or report.md
+            let result = fs::read_to_string(entry.path().join(RESULT_FILE))
+                .ok()
+                .and_then(|result_json| serde_json::from_str::<SummaryResult>(&result_json).ok());
+            let has_report = fs::read_to_string(entry.path().join(REPORT_FILE))
+                .is_ok_and(|report| report.contains("\n## Summary\n") && report.contains("\n## Highlights\n"));
+            let in_result = |file_name: &str| match (&result, file_name) {
+                (_, "summary.md" | "highlights.md") if has_report => true,
+                (Some(result), "summary.md") => !result.summary.trim().is_empty(),
+                (Some(result), "highlights.md") => !result.highlights.trim().is_empty(),
+                _ => false,
+            };
+            let missing: Vec<&'static str> = EXPECTED_OUTPUT_FILES
+                .into_iter()
+                .filter(|file_name| {
+                    fs::read_to_string(entry.path().join(file_name))
+                        .map(|content| content.trim().is_empty())
+                        .unwrap_or(true)
+                })
+                .filter(|file_name| !in_result(file_name))
+                .collect();
+            if !missing.is_empty() {
+                incomplete.push((entry.file_name().to_string_lossy().into_owned(), missing));
+            }
+        }
+    
+        incomplete.sort();
+        Ok(incomplete)
+    }
+
+    /// How long ago a video's transcript.txt was last written, if it exists
+    pub fn transcript_age(&self, video_id: &str) -> Option<Duration> {
+        let modified = fs::metadata(self.video_dir(video_id).join("transcript.txt")).ok()?.modified().ok()?;
+        // A modification time in the future (clock skew) counts as brand new
+        Some(modified.elapsed().unwrap_or_default())
+    }
+
+    /// Loads a video's metadata and transcript from the output directory without touching the network.
+    /// Directories from before metadata.json existed fall back to the title and description in info.md.
+    /// Returns `None` if anything needed (transcript.txt, and metadata.json or info.md) is missing.
+    pub fn load_cached_metadata(&self, video_id: &str) -> Option<VideoMetadata> {
+        let video_dir = self.video_dir(video_id);
+        let transcript = fs::read_to_string(video_dir.join("transcript.txt")).ok()?;
+        if transcript.trim().is_empty() {
+            return None;
+        }
+    
+        let cached = match fs::read_to_string(video_dir.join("metadata.json")) {
+            Ok(metadata_json) => serde_json::from_str::<CachedMetadata>(&metadata_json).ok()?,
+            Err(_) => {
+                let (title, description) = parse_info_md(&fs::read_to_string(video_dir.join("info.md")).ok()?)?;
+                CachedMetadata {
+                    video_id: video_id.to_string(),
+                    title,
+                    description,
+                    etag: None,
+                    language: None,
+                    segments: Vec::new(),
+                    chapters: Vec::new(),
+                    details: VideoDetails::default(),
+                    caption_settings: CaptionSettings::default(),
+                }
+            }
+        };
+        if cached.video_id != video_id {
+            return None;
+        }
+    
+        Some(VideoMetadata {
+            video_id: cached.video_id,
+            title: cached.title,
+            description: cached.description,
+            transcript,
+            etag: cached.etag,
+            language: cached.language,
+            segments: cached.segments,
+            chapters: cached.chapters,
+            details: cached.details,
+            raw_transcript: None,
+            caption_settings: cached.caption_settings,
+        })
+    }
+
+    /// Loads a video's cached metadata like `load_cached_metadata`, but only if it was fetched
+    /// the way `options` asks: a transcript in another language than the one requested (or
+    /// translated into), or made with other caption settings, doesn't count
+    pub fn load_cached_metadata_for(&self, video_id: &str, options: &FetchOptions) -> Option<VideoMetadata> {
+        self.load_cached_metadata(video_id).filter(|cached| {
+            let same_language = match (options.translate.as_ref().or(options.language.as_ref()), &cached.language) {
+                (None, _) => true,
+                (Some(wanted), Some(language)) => transcript::same_language(wanted, language),
+                (Some(_), None) => false,
+            };
+            same_language && cached.caption_settings == CaptionSettings::requested(options)
+        })
+    }
+}
+
+/// A summary and highlights already written for a video, with the models that wrote them
+#[derive(Debug, PartialEq)]
+pub struct SavedOutputs {
+    pub summary: String,
+    pub summary_model: String,
+    pub highlights: String,
+    pub highlights_model: String,
+}
+
+impl Workspace {
+    /// Loads the summary and highlights a previous run wrote for a video, from result.json or else
+    /// from summary.md and highlights.md. Returns `None` unless both are there, non-empty, and
+    /// were generated with the given settings (model, focus, length, ...).
+    pub fn load_saved_outputs(&self, video_id: &str, summary_settings: &str, highlights_settings: &str) -> Option<SavedOutputs> {
+        let video_dir = self.video_dir(video_id);
+        let result = fs::read_to_string(video_dir.join(RESULT_FILE))
+            .ok()
+            .and_then(|result_json| serde_json::from_str::<SummaryResult>(&result_json).ok())
+            .filter(|result| !result.summary.trim().is_empty() && !result.highlights.trim().is_empty())
+            .filter(|result| result.summary_settings == summary_settings && result.highlights_settings == highlights_settings);
+        if let Some(result) = result {
+            return Some(SavedOutputs {
+                summary: result.summary,
+                summary_model: result.summary_model,
+                highlights: result.highlights,
+                highlights_model: result.highlights_model,
+            });
+        }
+    
+        let summary = self.load_saved_output(video_id, "summary.md", summary_settings)?;
+        let highlights = self.load_saved_output(video_id, "highlights.md", highlights_settings)?;
+        Some(SavedOutputs {
+            summary,
+            summary_model: settings_model(summary_settings)?.to_string(),
+            highlights,
+            highlights_model: settings_model(highlights_settings)?.to_string(),
+        })
+    }
+
+    /// Loads the body of a Markdown output (e.g. summary.md) a previous run wrote for a video,
+    /// unless it's empty or was generated with other settings
+    pub fn load_saved_output(&self, video_id: &str, file_name: &str, settings: &str) -> Option<String> {
+        let (saved_settings, body) = parse_output_md(&fs::read_to_string(self.video_dir(video_id).join(file_name)).ok()?)?;
+        (saved_settings == settings).then_some(body)
+    }
+}
+
+/// A video's summary as it goes in the digest
+#[derive(Debug, Clone)]
+pub struct DigestEntry {
+    pub video_id: String,
+    pub title: String,
+    pub details: VideoDetails,
+    /// The summary's Markdown
+    pub summary: String,
+}
+
+impl Workspace {
+    /// Loads the summary of every video in the output directory, sorted by title, and counts the
+    /// videos skipped for having no summary (or no metadata to title it with)
+    pub fn load_digest_entries(&self) -> Result<(Vec<DigestEntry>, usize)> {
+        let mut entries = Vec::new();
+        let mut skipped = 0;
+        for entry in fs::read_dir(&self.root).context("Failed to read output directory")? {
+            let entry = entry.context("Failed to read output directory entry")?;
+            if !entry.path().is_dir() {
+                continue;
+            }
+            match self.load_digest_entry(&entry.file_name().to_string_lossy()) {
+                Some(digest_entry) => entries.push(digest_entry),
+                None => skipped += 1,
+            }
+        }
+        entries.sort_by_key(|entry| entry.title.to_lowercase());
+        Ok((entries, skipped))
+    }
+
+    /// Loads a video's summary, from result.json or else summary.md, with its title and details;
+    /// `None` without a summary or the metadata to title it with
+    pub fn load_digest_entry(&self, video_id: &str) -> Option<DigestEntry> {
+        let video_dir = self.video_dir(video_id);
+        let summary = fs::read_to_string(video_dir.join(RESULT_FILE))
+            .ok()
+            .and_then(|result_json| serde_json::from_str::<SummaryResult>(&result_json).ok())
+            .map(|result| result.summary)
+            .filter(|summary| !summary.trim().is_empty())
+            .or_else(|| parse_output_md(&fs::read_to_string(video_dir.join("summary.md")).ok()?).map(|(_, summary)| summary))?;
+        let metadata = self.load_cached_metadata(video_id)?;
+        Some(DigestEntry { video_id: video_id.to_string(), title: metadata.title, details: metadata.details, summary })
+    }
+}
+
+/// Formats monthly-review.md: the header, the period as the title, the review, then a list of
+/// the videos it covers
+pub fn format_review(header: &str, period: &str, review: &str, entries: &[DigestEntry]) -> String {
+    let mut content = format!("{}\n\n# Review: {}\n\n{}\n\n## Videos included\n\n", header, period, review.trim());
+    for entry in entries {
+        let date = entry.details.publish_date.as_ref().or(entry.details.upload_date.as_ref());
+        content.push_str(&format!(
+            "- [{}](https://www.youtube.com/watch?v={}){}\n",
+            entry.title,
+            entry.video_id,
+            date.map_or(String::new(), |date| format!(" ({})", date))
+        ));
+    }
+    content
+}
+
+/// Formats the summaries as one self-contained HTML page: a table of contents linking to each
+/// video, then each video's title, details and summary. The summaries' own headings are moved
+/// below the video titles.
+pub fn format_digest(entries: &[DigestEntry]) -> String {
+    use pulldown_cmark::{Event, HeadingLevel, Parser, Tag, TagEnd};
+    let escape = |text: &str| html_escape::encode_text(text).into_owned();
+    // A video's title is an <h2>, so the summary's headings move down two levels, keeping their hierarchy
+    let demote = |level: HeadingLevel| HeadingLevel::try_from((level as usize + 2).min(6)).unwrap_or(level);
+
+    let mut html = String::from(
+        "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>Video summaries</title>\n\
+         <style>body { max-width: 40em; margin: auto; padding: 1em; font-family: serif; line-height: 1.5; } .details { color: #555; }</style>\n\
+         </head>\n<body>\n<h1>Video summaries</h1>\n<nav>\n<ul>\n"
+    );
+    for entry in entries {
+        html.push_str(&format!("<li><a href=\"#video-{}\">{}</a></li>\n", escape(&entry.video_id), escape(&entry.title)));
+    }
+    html.push_str("</ul>\n</nav>\n");
+
+    for entry in entries {
+        html.push_str(&format!("<section id=\"video-{}\">\n<h2>{}</h2>\n", escape(&entry.video_id), escape(&entry.title)));
+        let details = &entry.details;
+        let mut facts: Vec<String> = [details.channel.as_ref(), details.upload_date.as_ref()]
+            .into_iter()
+            .flatten()
+            .map(|fact| escape(fact))
+            .collect();
+        // Local files and transcript files have no video to link to
+        if extract_video_id(&format!("https://youtu.be/{}", entry.video_id)).is_ok_and(|id| id == entry.video_id) {
+            facts.push(format!("<a href=\"https://www.youtube.com/watch?v={}\">Watch on YouTube</a>", entry.video_id));
+        }
+        if !facts.is_empty() {
+            html.push_str(&format!("<p class=\"details\">{}</p>\n", facts.join(" · ")));
+        }
+        let events = Parser::new(&entry.summary).map(|event| match event {
+            Event::Start(Tag::Heading { level, id, classes, attrs }) => Event::Start(Tag::Heading { level: demote(level), id, classes, attrs }),
+            Event::End(TagEnd::Heading(level)) => Event::End(TagEnd::Heading(demote(level))),
+            // The summary is model output, so raw HTML in it is shown as text rather than rendered
+            Event::Html(html) | Event::InlineHtml(html) => Event::Text(html),
+            event => event,
+        });
+        pulldown_cmark::html::push_html(&mut html, events);
+        html.push_str("</section>\n");
+    }
+    html.push_str("</body>\n</html>\n");
+    html
+}
+
+/// Reads the settings (the `_Generated: ... · Model: ..._` header without its timestamp and finish
+/// reason) and the text below the header back out of a summary.md or highlights.md
+fn parse_output_md(content: &str) -> Option<(String, String)> {
+    let (header, body) = content.split_once("\n\n")?;
+    let settings: Vec<&str> = header.trim()
+        .strip_prefix("_Generated: ")?
+        .strip_suffix('_')?
+        .split(" · ")
+        .skip(1)
+        .filter(|field| !field.starts_with("Finish reason: "))
+        .collect();
+    settings_model(&settings.join(" · "))?;
+    let body = body.trim();
+    if body.is_empty() {
+        return None;
+    }
+    Some((settings.join(" · "), body.to_string()))
 }
 
-/// Gets the transcript path for a video
-pub fn get_transcript_path(video_id: &str) -> PathBuf {
-    Path::new("output").join(video_id).join("transcript.txt")
+/// The model named in a header's settings, e.g. "gpt-4o" in "Model: gpt-4o · Focus: testing"
+fn settings_model(settings: &str) -> Option<&str> {
+    settings.split(" · ").next()?.strip_prefix("Model: ")
+}
+
+/// Formats report.md: the title and details, then the description, summary and highlights, with the
+/// long description and transcript in collapsible sections
+fn format_report(metadata: &VideoMetadata, summary: &str, highlights: &str, transcript: Option<&str>) -> String {
+    let mut report = format!("# {}\n\n{}", metadata.title, format_video_details(metadata));
+    report.push_str(&format!(
+        "<details>\n<summary>Description</summary>\n\n{}\n\n</details>\n\n",
+        metadata.description.trim()
+    ));
+    report.push_str(&format!("## Summary\n\n{}\n\n## Highlights\n\n{}\n", summary.trim(), highlights.trim()));
+    if let Some(transcript) = transcript {
+        report.push_str(&format!("\n## Transcript\n\n<details>\n<summary>Show the full transcript</summary>\n\n{}\n\n</details>\n", transcript.trim()));
+    }
+    report
+}
+
+/// Formats a video's details and transcript language as the list under the title in info.md,
+/// or nothing if there are none
+fn format_video_details(metadata: &VideoMetadata) -> String {
+    let details = &metadata.details;
+    let mut lines = Vec::new();
+    match (&details.channel, &details.channel_id) {
+        (Some(channel), Some(channel_id)) => lines.push(format!("- **Channel:** [{}](https://www.youtube.com/channel/{})", channel, channel_id)),
+        (Some(channel), None) => lines.push(format!("- **Channel:** {}", channel)),
+        (None, _) => {}
+    }
+    if let Some(duration) = details.duration {
+        lines.push(format!("- **Duration:** {}", transcript::format_youtube_timestamp(duration as f64)));
+    }
+    if let Some(view_count) = details.view_count {
+        lines.push(format!("- **Views:** {}", format_count(view_count)));
+    }
+    if let Some(upload_date) = &details.upload_date {
+        lines.push(format!("- **Uploaded:** {}", upload_date));
+    }
+    if let Some(publish_date) = details.publish_date.as_ref().filter(|date| details.upload_date.as_ref() != Some(*date)) {
+        lines.push(format!("- **Published:** {}", publish_date));
+    }
+    if details.live_stream == Some(true) {
+        lines.push("- **Live stream:** Recording of a past broadcast".to_string());
+    }
+    if let Some(language) = &metadata.language {
+        let label = transcript::language_name(language)
+            .map_or_else(|| language.clone(), |name| format!("{} ({})", name, language));
+        lines.push(format!("- **Language:** {}", label));
+    }
+    match details.auto_generated_captions {
+        Some(true) => lines.push("- **Captions:** Auto-generated".to_string()),
+        Some(false) => lines.push("- **Captions:** Manual".to_string()),
+        None => {}
+    }
+    if lines.is_empty() {
+        return String::new();
+    }
+    format!("{}\n\n", lines.join("\n"))
+}
+
+/// Formats a number with thousands separators, e.g. 1,234,567
+pub fn format_count(count: u64) -> String {
+    let digits = count.to_string();
+    let mut formatted = String::new();
+    for (index, digit) in digits.chars().enumerate() {
+        if index > 0 && (digits.len() - index).is_multiple_of(3) {
+            formatted.push(',');
+        }
+        formatted.push(digit);
+    }
+    formatted
+}
+
+/// Reads the title (the first `# ` heading) and description (the rest, after any details list)
+/// back out of info.md
+fn parse_info_md(content: &str) -> Option<(String, String)> {
+    let (first_line, rest) = content.split_once('\n').unwrap_or((content, ""));
+    let title = first_line.strip_prefix("# ")?.trim();
+    if title.is_empty() {
+        return None;
+    }
+    // The details block (channel, views, ...) sits between the title's blank line and the next one
+    let description = match rest.strip_prefix('\n').filter(|body| body.starts_with("- **")) {
+        Some(body) => body.split_once("\n\n").map_or("", |(_, description)| description),
+        None => rest,
+    };
+    Some((title.to_string(), description.trim().to_string()))
+}
+
+/// Hashes a file's contents into a short, stable hex identifier (64-bit FNV-1a)
+pub fn hash_file_contents(path: &Path) -> Result<String> {
+    let file = fs::File::open(path).context(format!("Failed to read file: {}", path.display()))?;
+    let mut reader = BufReader::new(file);
+    
+    // Read in chunks, as the file can be a video of several gigabytes
+    let mut hash: u64 = 0xcbf29ce484222325;
+    loop {
+        let chunk = reader.fill_buf().context(format!("Failed to read file: {}", path.display()))?;
+        if chunk.is_empty() {
+            break;
+        }
+        for &byte in chunk {
+            hash ^= u64::from(byte);
+            hash = hash.wrapping_mul(0x100000001b3);
+        }
+        let length = chunk.len();
+        reader.consume(length);
+    }
+    
+    Ok(format!("{:016x}", hash))
+}
+
+/// Turns a file name into a folder-safe video ID, replacing anything but letters, digits, `-` and `_`
+pub fn sanitize_video_id(name: &str) -> String {
+    name.trim()
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() || c == '-' || c == '_' { c } else { '_' })
+        .collect()
+}
+
+/// Validates a strftime-style date format string (used as a clap value parser)
+pub fn parse_date_format(format: &str) -> Result<String> {
+    if StrftimeItems::new(format).any(|item| matches!(item, Item::Error)) {
+        return Err(SummariserError::ParseError(format!("Invalid date format: {}", format), None));
+    }
+    Ok(format.to_string())
+}
+
+/// Parses a date such as `2024-05-01` (used as a clap value parser)
+pub fn parse_date(value: &str) -> Result<NaiveDate> {
+    NaiveDate::parse_from_str(value.trim(), "%Y-%m-%d")
+        .map_err(|_| SummariserError::ParseError(format!("Invalid date: {} (expected YYYY-MM-DD, e.g. 2024-05-01)", value), None))
+}
+
+/// Parses a cache age such as `90s`, `30m`, `12h`, `7d` or `2w` (used as a clap value parser)
+pub fn parse_max_age(value: &str) -> Result<Duration> {
+    let value = value.trim();
+    let split = value.find(|c: char| !c.is_ascii_digit()).unwrap_or(value.len());
+    let (number, unit) = value.split_at(split);
+    let number: u64 = number.parse()
+        .map_err(|_| SummariserError::ParseError(format!("Invalid age: {} (expected a number and a unit, e.g. 7d)", value), None))?;
+    let seconds = match unit.trim() {
+        "s" => 1,
+        "m" => 60,
+        "h" => 60 * 60,
+        "d" => 24 * 60 * 60,
+        "w" => 7 * 24 * 60 * 60,
+        _ => return Err(SummariserError::ParseError(format!("Invalid age unit in {} (use s, m, h, d or w)", value), None)),
+    };
+    Ok(Duration::from_secs(number.saturating_mul(seconds)))
+}
+
+/// Formats the current time for output headers.
+/// Defaults to an ISO-8601 UTC timestamp; a custom format uses local time unless `utc` is set.
+pub fn format_timestamp(format: Option<&str>, utc: bool) -> String {
+    match format {
+        None => Utc::now().to_rfc3339_opts(SecondsFormat::Secs, true),
+        Some(format) if utc => Utc::now().format(format).to_string(),
+        Some(format) => Local::now().format(format).to_string(),
+    }
 }
 
 #[cfg(test)]
@@ -105,6 +990,20 @@ mod tests {
             ("https://youtu.be/dQw4w9WgXcQ", Ok("dQw4w9WgXcQ".to_string())),
             ("https://youtube.com/shorts/dQw4w9WgXcQ", Ok("dQw4w9WgXcQ".to_string())),
             ("https://www.youtube.com/embed/dQw4w9WgXcQ", Ok("dQw4w9WgXcQ".to_string())),
+            ("https://youtu.be/dQw4w9WgXcQ?t=42", Ok("dQw4w9WgXcQ".to_string())),
+            ("https://youtu.be/dQw4w9WgXcQ?si=AbC123xyz", Ok("dQw4w9WgXcQ".to_string())),
+            ("https://www.youtube.com/watch?v=dQw4w9WgXcQ&list=PLx0sYbCqOb8TBPRdmBHs5Iftvv9TPboYG&index=3", Ok("dQw4w9WgXcQ".to_string())),
+            ("https://www.youtube.com/watch?v=dQw4w9WgXcQ&feature=youtu.be", Ok("dQw4w9WgXcQ".to_string())),
+            ("https://www.youtube.com/watch?v=dQw4w9WgXcQ#t=42", Ok("dQw4w9WgXcQ".to_string())),
+            ("https://www.youtube.com/watch?feature=share&v=dQw4w9WgXcQ&t=42s", Ok("dQw4w9WgXcQ".to_string())),
+            ("https://m.youtube.com/watch?app=desktop&v=dQw4w9WgXcQ", Ok("dQw4w9WgXcQ".to_string())),
+            ("https://www.youtube.com/live/jfKfPfyJRdk?feature=share", Ok("jfKfPfyJRdk".to_string())),
+            ("https://youtube.com/shorts/dQw4w9WgXcQ?feature=share", Ok("dQw4w9WgXcQ".to_string())),
+            ("https://www.youtube-nocookie.com/embed/dQw4w9WgXcQ?start=42", Ok("dQw4w9WgXcQ".to_string())),
+            ("https://www.youtube.com/watch?v=dQw4w9WgX", Err(())),
+            ("https://www.youtube.com/watch?v=dQw4w9WgXcQxyz", Err(())),
+            ("https://www.youtube.com/watch?list=PLx0sYbCqOb8TBPRdmBHs5Iftvv9TPboYG&v=", Err(())),
+            ("https://www.youtube.com/watch?t=42#v=dQw4w9WgXcQ", Err(())),
             ("https://invalid-url.com", Err(())),
         ];
 
@@ -116,4 +1015,317 @@ mod tests {
             }
         }
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_parse_video_ids() {
+        let list = "dQw4w9WgXcQ, https://youtu.be/jfKfPfyJRdk\n# skipped for now\nabc-_123XYZ  # trailing comment\n\ndQw4w9WgXcQ";
+        assert_eq!(parse_video_ids(list).unwrap(), vec!["dQw4w9WgXcQ", "jfKfPfyJRdk", "abc-_123XYZ"]);
+        assert!(parse_video_ids("").unwrap().is_empty());
+        assert!(matches!(parse_video_ids("dQw4w9WgXcQ,example.com/video"), Err(SummariserError::InvalidUrl(_))));
+    }
+
+    #[test]
+    fn test_extract_playlist_id() {
+        let playlist = "https://www.youtube.com/playlist?list=PLx0sYbCqOb8TBPRdmBHs5Iftvv9TPboYG";
+        assert_eq!(extract_playlist_id(playlist).as_deref(), Some("PLx0sYbCqOb8TBPRdmBHs5Iftvv9TPboYG"));
+        assert!(is_playlist_url(playlist));
+
+        let watch_in_playlist = "https://www.youtube.com/watch?v=dQw4w9WgXcQ&list=PLx0sYbCqOb8TBPRdmBHs5Iftvv9TPboYG";
+        assert!(extract_playlist_id(watch_in_playlist).is_some());
+        assert!(!is_playlist_url(watch_in_playlist));
+
+        assert_eq!(extract_playlist_id("https://youtu.be/dQw4w9WgXcQ"), None);
+    }
+
+    #[test]
+    fn test_extract_channel_path() {
+        assert_eq!(extract_channel_path("https://www.youtube.com/@RustTalks").as_deref(), Some("@RustTalks"));
+        assert_eq!(extract_channel_path("https://www.youtube.com/@RustTalks/videos").as_deref(), Some("@RustTalks"));
+        assert_eq!(extract_channel_path("https://www.youtube.com/channel/UCaYhcUwRBNscFNUKTjgPFiA").as_deref(), Some("channel/UCaYhcUwRBNscFNUKTjgPFiA"));
+        assert_eq!(extract_channel_path("https://www.youtube.com/user/rustlang?app=desktop").as_deref(), Some("user/rustlang"));
+        assert_eq!(extract_channel_path("https://www.youtube.com/watch?v=dQw4w9WgXcQ"), None);
+        assert_eq!(extract_channel_path("https://www.youtube.com/playlist?list=PLx0sYbCqOb8TBPRdmBHs5Iftvv9TPboYG"), None);
+
+        assert_eq!(channel_url("@RustTalks"), "https://www.youtube.com/@RustTalks");
+        assert_eq!(channel_url("RustTalks"), "https://www.youtube.com/@RustTalks");
+        assert_eq!(channel_url("UCaYhcUwRBNscFNUKTjgPFiA"), "https://www.youtube.com/channel/UCaYhcUwRBNscFNUKTjgPFiA");
+        assert_eq!(channel_url("https://www.youtube.com/c/rustlang"), "https://www.youtube.com/c/rustlang");
+    }
+
+    #[test]
+    fn test_load_saved_outputs() {
+        let root = std::env::temp_dir().join(format!("saved-outputs-test-{}", std::process::id()));
+        let workspace = Workspace::new(&root);
+        fs::create_dir_all(root.join("abc")).unwrap();
+        workspace.save_summary("abc", "_Generated: 2024-05-01 · Model: gpt-4o · Focus: testing · Finish reason: stop_\n\nSummary").unwrap();
+        workspace.save_highlights("abc", "_Generated: 2024-05-01 · Model: gpt-4o_\n\n- Highlight").unwrap();
+
+        let saved = workspace.load_saved_outputs("abc", "Model: gpt-4o · Focus: testing", "Model: gpt-4o").unwrap();
+        assert_eq!(saved.summary, "Summary");
+        assert_eq!(saved.summary_model, "gpt-4o");
+        assert_eq!(saved.highlights, "- Highlight");
+        // Another focus, length or model means the summary has to be regenerated
+        assert_eq!(workspace.load_saved_outputs("abc", "Model: gpt-4o · Focus: security", "Model: gpt-4o"), None);
+        assert_eq!(workspace.load_saved_outputs("abc", "Model: gpt-4o · Focus: testing", "Model: gpt-4o · Length: short"), None);
+
+        workspace.save_output("abc", "context-digest.md", "_Generated: 2024-05-01 · Model: gpt-4o-mini_\n\n- Notes").unwrap();
+        assert_eq!(workspace.load_saved_output("abc", "context-digest.md", "Model: gpt-4o-mini").as_deref(), Some("- Notes"));
+        assert_eq!(workspace.load_saved_output("abc", "context-digest.md", "Model: gpt-4o"), None);
+        assert_eq!(workspace.load_saved_output("abc", "missing.md", "Model: gpt-4o-mini"), None);
+        fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn test_load_cached_metadata_for() {
+        let root = std::env::temp_dir().join(format!("cached-metadata-test-{}", std::process::id()));
+        let workspace = Workspace::new(&root);
+        let metadata = VideoMetadata {
+            description: "A talk.".to_string(),
+            language: Some("pt".to_string()),
+            ..VideoMetadata::for_test("abc", "Olá a todos")
+        };
+        workspace.save_video_files(&metadata, TranscriptFormat::Plain).unwrap();
+
+        let options = |language: Option<&str>, translate: Option<&str>| FetchOptions {
+            language: language.map(str::to_string),
+            translate: translate.map(str::to_string),
+            ..FetchOptions::default()
+        };
+        assert!(workspace.load_cached_metadata_for("abc", &options(None, None)).is_some());
+        // The same language under another code still counts
+        assert!(workspace.load_cached_metadata_for("abc", &options(Some("pt-BR"), None)).is_some());
+        assert!(workspace.load_cached_metadata_for("abc", &options(Some("por"), None)).is_some());
+        assert!(workspace.load_cached_metadata_for("abc", &options(Some("en"), None)).is_none());
+        assert!(workspace.load_cached_metadata_for("abc", &options(Some("pt"), Some("en"))).is_none());
+        // A transcript taken from another named track doesn't count either
+        let named_track = FetchOptions { track_name: Some("Português (Brasil)".to_string()), ..FetchOptions::default() };
+        assert!(workspace.load_cached_metadata_for("abc", &named_track).is_none());
+        let metadata = VideoMetadata { caption_settings: CaptionSettings::requested(&named_track), ..metadata };
+        workspace.save_video_files(&metadata, TranscriptFormat::Plain).unwrap();
+        assert!(workspace.load_cached_metadata_for("abc", &named_track).is_some());
+        assert!(workspace.load_cached_metadata_for("abc", &options(None, None)).is_none());
+        // Nor one merged into blocks of another size
+        let merged = FetchOptions { merge_segments: Some(500), ..named_track.clone() };
+        assert!(workspace.load_cached_metadata_for("abc", &merged).is_none());
+        fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn test_failed_summary_keeps_previous_output() {
+        let root = std::env::temp_dir().join(format!("workspace-test-{}", std::process::id()));
+        let workspace = Workspace::new(&root);
+        let metadata = VideoMetadata {
+            description: "A talk.".to_string(),
+            language: Some("en".to_string()),
+            ..VideoMetadata::for_test("abc", "Hello everyone")
+        };
+        let video_dir = root.join("abc");
+
+        // Fetching alone doesn't leave an empty summary.md behind if the summary then fails
+        workspace.save_video_files(&metadata, TranscriptFormat::Plain).unwrap();
+        assert!(!video_dir.join("summary.md").exists());
+
+        // A later run that refetches and then fails to summarise keeps the good summary
+        workspace.save_summary("abc", "A good summary").unwrap();
+        workspace.save_video_files(&metadata, TranscriptFormat::Plain).unwrap();
+        assert_eq!(read_from_file(&video_dir.join("summary.md")).unwrap(), "A good summary");
+
+        // A write that fails (the target is a directory) leaves no temporary file behind
+        fs::create_dir(video_dir.join("highlights.md")).unwrap();
+        assert!(workspace.save_highlights("abc", "Highlights").is_err());
+        let mut files: Vec<String> = fs::read_dir(&video_dir).unwrap()
+            .map(|entry| entry.unwrap().file_name().to_string_lossy().into_owned())
+            .collect();
+        files.sort();
+        assert_eq!(files, ["highlights.md", "info.md", "metadata.json", "summary.md", "transcript.txt"]);
+
+        fs::remove_dir_all(root).ok();
+    }
+
+    #[test]
+    fn test_parse_info_md() {
+        assert_eq!(
+            parse_info_md("# Rust in 100 Seconds\n\nA quick tour.\n\n0:00 Intro"),
+            Some(("Rust in 100 Seconds".to_string(), "A quick tour.\n\n0:00 Intro".to_string()))
+        );
+        assert_eq!(parse_info_md("# Title only"), Some(("Title only".to_string(), String::new())));
+        assert_eq!(
+            parse_info_md("# Talk\n\n- **Channel:** RustConf\n- **Views:** 1,234,567\n\nA quick tour."),
+            Some(("Talk".to_string(), "A quick tour.".to_string()))
+        );
+        assert_eq!(
+            parse_info_md("# Talk\n- **Bold** opening line\n\nMore."),
+            Some(("Talk".to_string(), "- **Bold** opening line\n\nMore.".to_string()))
+        );
+        assert_eq!(
+            parse_info_md("# Talk\n\n- **Channel:** RustConf\n\n- **Step one:** install"),
+            Some(("Talk".to_string(), "- **Step one:** install".to_string()))
+        );
+        assert_eq!(format_count(1_234_567), "1,234,567");
+        assert_eq!(format_count(999), "999");
+        assert_eq!(parse_info_md("No heading\n\nText"), None);
+    }
+
+    #[test]
+    fn test_parse_output_md() {
+        assert_eq!(
+            parse_output_md("_Generated: 2024-05-01T12:30:00Z · Model: gpt-4o · Focus: testing_\n\nThe video covers...\n"),
+            Some(("Model: gpt-4o · Focus: testing".to_string(), "The video covers...".to_string()))
+        );
+        assert_eq!(
+            parse_output_md("_Generated: 2024-05-01T12:30:00Z · Model: gpt-4 · Finish reason: length_\n\n> **Note:** Partial.\n\nSummary"),
+            Some(("Model: gpt-4".to_string(), "> **Note:** Partial.\n\nSummary".to_string()))
+        );
+        assert_eq!(settings_model("Model: gpt-4o · Focus: testing"), Some("gpt-4o"));
+        assert_eq!(parse_output_md(""), None);
+        assert_eq!(parse_output_md("_Generated: 2024-05-01T12:30:00Z · Model: gpt-4_\n\n"), None);
+        assert_eq!(parse_output_md("Hand-written notes\n\nwithout a header"), None);
+    }
+
+    #[test]
+    fn test_format_report() {
+        let metadata = VideoMetadata {
+            description: "A quick tour.".to_string(),
+            details: VideoDetails { channel: Some("RustConf".to_string()), ..VideoDetails::default() },
+            ..VideoMetadata::for_test("abc", "Hello everyone")
+        };
+        let report = format_report(&metadata, "The summary.", "- A highlight\n", None);
+        assert_eq!(
+            report,
+            "# Talk\n\n- **Channel:** RustConf\n\n<details>\n<summary>Description</summary>\n\nA quick tour.\n\n</details>\n\n\
+             ## Summary\n\nThe summary.\n\n## Highlights\n\n- A highlight\n"
+        );
+        let report = format_report(&metadata, "The summary.", "- A highlight", Some("Hello everyone"));
+        assert!(report.ends_with("## Transcript\n\n<details>\n<summary>Show the full transcript</summary>\n\nHello everyone\n\n</details>\n"));
+
+        let metadata = VideoMetadata {
+            language: Some("pt".to_string()),
+            details: VideoDetails { auto_generated_captions: Some(true), ..metadata.details },
+            ..metadata
+        };
+        let report = format_report(&metadata, "The summary.", "- A highlight", Some("Hello everyone"));
+        assert!(report.starts_with("# Talk\n\n- **Channel:** RustConf\n- **Language:** Portuguese (pt)\n- **Captions:** Auto-generated\n\n"));
+        assert!(report.ends_with("## Transcript\n\n<details>\n<summary>Show the full transcript</summary>\n\nHello everyone\n\n</details>\n"));
+    }
+
+    #[test]
+    fn test_sanitize_video_id() {
+        assert_eq!(sanitize_video_id("dQw4w9WgXcQ"), "dQw4w9WgXcQ");
+        assert_eq!(sanitize_video_id("My Talk (2024).en"), "My_Talk__2024__en");
+        assert_eq!(sanitize_video_id("../escape"), "___escape");
+    }
+
+    #[test]
+    fn test_parse_max_age() {
+        assert_eq!(parse_max_age("90s").unwrap(), Duration::from_secs(90));
+        assert_eq!(parse_max_age("12h").unwrap(), Duration::from_secs(12 * 3600));
+        assert_eq!(parse_max_age("2w").unwrap(), Duration::from_secs(14 * 86400));
+        assert!(parse_max_age("7").is_err());
+        assert!(parse_max_age("d").is_err());
+        assert!(parse_max_age("3 months").is_err());
+    }
+
+    #[test]
+    fn test_parse_date() {
+        assert_eq!(parse_date("2024-05-01").unwrap(), NaiveDate::from_ymd_opt(2024, 5, 1).unwrap());
+        assert!(parse_date("2024-13-01").is_err());
+        assert!(parse_date("May 2024").is_err());
+    }
+
+    #[test]
+    fn test_parse_date_format() {
+        assert!(parse_date_format("%Y-%m-%d %H:%M").is_ok());
+        assert!(parse_date_format("%Q").is_err());
+    }
+
+    #[test]
+    fn test_extract_description_chapters() {
+        let description = "Great talk!\n0:00 Intro\n1:05:30 - Q&A\n(12:34) Deep dive\nVisit example.com";
+        assert_eq!(
+            extract_description_chapters(description),
+            vec!["0:00 Intro", "1:05:30 - Q&A", "(12:34) Deep dive"]
+        );
+        assert_eq!(parse_description_chapters(description), vec![
+            Chapter { start: 0.0, title: "Intro".to_string() },
+            Chapter { start: 3930.0, title: "Q&A".to_string() },
+            Chapter { start: 754.0, title: "Deep dive".to_string() },
+        ]);
+    }
+
+    #[test]
+    fn test_format_digest() {
+        let entry = |video_id: &str, title: &str, summary: &str| DigestEntry {
+            video_id: video_id.to_string(),
+            title: title.to_string(),
+            details: VideoDetails { channel: Some("RustConf".to_string()), ..Default::default() },
+            summary: summary.to_string(),
+        };
+        let html = format_digest(&[
+            entry("dQw4w9WgXcQ", "Ownership & borrowing", "# Overview\n\n## Key points\n\n- **Moves** transfer ownership"),
+            entry("audio-1f3a9c0d2b4e5f67", "Recording", "A <b>talk</b>.\n\n<script>alert(1)</script>"),
+        ]);
+        assert!(html.contains("<li><a href=\"#video-dQw4w9WgXcQ\">Ownership &amp; borrowing</a></li>"));
+        assert!(html.contains("<section id=\"video-dQw4w9WgXcQ\">\n<h2>Ownership &amp; borrowing</h2>"));
+        assert!(html.contains("<h3>Overview</h3>\n<h4>Key points</h4>"));
+        assert!(html.contains("A &lt;b&gt;talk&lt;/b&gt;."));
+        assert!(html.contains("&lt;script&gt;alert(1)&lt;/script&gt;"));
+        assert!(!html.contains("<script>"));
+        assert!(html.contains("<li><strong>Moves</strong> transfer ownership</li>"));
+        assert!(html.contains("RustConf · <a href=\"https://www.youtube.com/watch?v=dQw4w9WgXcQ\">Watch on YouTube</a>"));
+        assert!(!html.contains("watch?v=audio-"));
+    }
+
+    #[test]
+    fn test_format_review() {
+        let entries = [DigestEntry {
+            video_id: "dQw4w9WgXcQ".to_string(),
+            title: "Ownership".to_string(),
+            details: VideoDetails { upload_date: Some("2024-05-01".to_string()), publish_date: Some("2024-05-03".to_string()), ..Default::default() },
+            summary: "Summary".to_string(),
+        }];
+        let review = format_review("_Generated: 2024-06-01 · Model: gpt-4o_", "2024-05-01 to 2024-05-31", "## Themes\n\nMemory safety\n", &entries);
+        assert_eq!(
+            review,
+            "_Generated: 2024-06-01 · Model: gpt-4o_\n\n# Review: 2024-05-01 to 2024-05-31\n\n## Themes\n\nMemory safety\n\n\
+             ## Videos included\n\n- [Ownership](https://www.youtube.com/watch?v=dQw4w9WgXcQ) (2024-05-03)\n"
+        );
+    }
+
+    #[test]
+    fn test_split_comment() {
+        let text = "0:00 Intro — hello\n1:30 Setup — tools\n\n5:00 Wrap-up — bye";
+        assert_eq!(split_comment(text, 100), vec![text.replace("\n\n", "\n")]);
+        assert_eq!(
+            split_comment(text, 37),
+            vec!["0:00 Intro — hello\n1:30 Setup — tools", "5:00 Wrap-up — bye"]
+        );
+        assert_eq!(split_comment("abcdefgh", 3), vec!["abc", "def", "gh"]);
+        assert!(format_comment_blocks(&["a".to_string(), "b".to_string()]).contains("----- Comment 2/2 -----\nb\n"));
+    }
+
+    #[test]
+    fn test_temp_file() {
+        let first = TempFile::create("temp-file-test", "bin", b"audio").unwrap();
+        let second = TempFile::create("temp-file-test", "bin", b"other").unwrap();
+        assert_ne!(first.path(), second.path());
+        assert_eq!(fs::read(first.path()).unwrap(), b"audio");
+        assert!(first.path().file_name().unwrap().to_string_lossy().starts_with(&format!("temp-file-test-{}-", std::process::id())));
+
+        let path = first.path().to_path_buf();
+        drop(first);
+        assert!(!path.exists());
+    }
+
+    #[test]
+    fn test_hash_file_contents() {
+        let path = std::env::temp_dir().join(format!("hash-test-{}.bin", std::process::id()));
+        fs::write(&path, "a").unwrap();
+        // The 64-bit FNV-1a hash of "a"
+        assert_eq!(hash_file_contents(&path).unwrap(), "af63dc4c8601ec8c");
+        // Larger than the reader's buffer, so it's hashed across several chunks
+        fs::write(&path, vec![b'x'; 20_000]).unwrap();
+        let expected = (0..20_000).fold(0xcbf29ce484222325u64, |hash, _| (hash ^ u64::from(b'x')).wrapping_mul(0x100000001b3));
+        assert_eq!(hash_file_contents(&path).unwrap(), format!("{:016x}", expected));
+        fs::remove_file(&path).unwrap();
+    }
+}